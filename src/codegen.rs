@@ -0,0 +1,231 @@
+use crate::error::TreeError;
+use crate::feature_encoding::FeatureTimeUnit;
+use polars_core::datatypes::DataType;
+use polars_core::schema::Schema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Words that can't be used as a field name, these get a trailing underscore:
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+    "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while",
+];
+
+/// The Rust type of a feature in the generated row struct, taken from the training schema.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FieldType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    String,
+    Bool,
+    // Days since 1970-01-01:
+    Date,
+    // Time units since 1970-01-01 00:00:00 UTC:
+    Datetime(FeatureTimeUnit),
+    Duration(FeatureTimeUnit),
+}
+
+impl FieldType {
+    fn from_dtype(column: &str, dtype: &DataType) -> Result<Self, TreeError> {
+        match dtype {
+            DataType::Int8 => Ok(FieldType::I8),
+            DataType::Int16 => Ok(FieldType::I16),
+            DataType::Int32 => Ok(FieldType::I32),
+            DataType::Int64 => Ok(FieldType::I64),
+            DataType::UInt8 => Ok(FieldType::U8),
+            DataType::UInt16 => Ok(FieldType::U16),
+            DataType::UInt32 => Ok(FieldType::U32),
+            DataType::UInt64 => Ok(FieldType::U64),
+            DataType::Float32 => Ok(FieldType::F32),
+            DataType::Float64 => Ok(FieldType::F64),
+            DataType::String | DataType::Categorical(_, _) | DataType::Enum(_, _) => {
+                Ok(FieldType::String)
+            }
+            DataType::Boolean => Ok(FieldType::Bool),
+            DataType::Date => Ok(FieldType::Date),
+            DataType::Datetime(time_unit, _) => Ok(FieldType::Datetime((*time_unit).into())),
+            DataType::Duration(time_unit) => Ok(FieldType::Duration((*time_unit).into())),
+            _ => Err(TreeError::UnsupportedDtype {
+                column: column.to_string(),
+                dtype: dtype.clone(),
+            }),
+        }
+    }
+
+    fn as_rust(&self) -> &'static str {
+        match self {
+            FieldType::I8 => "i8",
+            FieldType::I16 => "i16",
+            FieldType::I32 => "i32",
+            FieldType::I64 => "i64",
+            FieldType::U8 => "u8",
+            FieldType::U16 => "u16",
+            FieldType::U32 => "u32",
+            FieldType::U64 => "u64",
+            FieldType::F32 => "f32",
+            FieldType::F64 => "f64",
+            FieldType::String => "String",
+            FieldType::Bool => "bool",
+            FieldType::Date => "i32",
+            FieldType::Datetime(_) | FieldType::Duration(_) => "i64",
+        }
+    }
+
+    // Temporal features are passed as integers, the generated code says in which unit:
+    fn get_doc_comment(&self) -> Option<String> {
+        match self {
+            FieldType::Date => Some("Days since 1970-01-01.".to_string()),
+            FieldType::Datetime(time_unit) => Some(format!(
+                "{} since 1970-01-01 00:00:00 UTC.",
+                time_unit.as_str()
+            )),
+            FieldType::Duration(time_unit) => Some(format!("Duration in {}.", time_unit.as_str())),
+            _ => None,
+        }
+    }
+}
+
+/// A feature the tree was fitted on, kept to generate the row struct.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Field {
+    pub(crate) column: String,
+    pub(crate) field_type: FieldType,
+}
+
+/// Reads the types of the features as they are passed in from the input schema, leaving out the
+/// columns that are not in the schema of the preprocessed features.
+pub(crate) fn get_fields(
+    input_schema: &Schema,
+    preprocessed_schema: &Schema,
+) -> Result<Vec<Field>, TreeError> {
+    input_schema
+        .iter()
+        .filter(|(name, _)| preprocessed_schema.contains(name.as_str()))
+        .map(|(name, dtype)| {
+            Ok(Field {
+                column: name.to_string(),
+                field_type: FieldType::from_dtype(name, dtype)?,
+            })
+        })
+        .collect()
+}
+
+/// Turns a column name into a snake case field name, e.g. "PassengerId" into "passenger_id".
+pub(crate) fn get_field_name(column: &str) -> String {
+    let mut field_name = String::new();
+    let mut previous: Option<char> = None;
+    for character in column.chars() {
+        if character.is_ascii_alphanumeric() {
+            let starts_word = character.is_ascii_uppercase()
+                && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit());
+            if starts_word {
+                field_name.push('_');
+            }
+            field_name.push(character.to_ascii_lowercase());
+        } else if !field_name.ends_with('_') {
+            field_name.push('_');
+        }
+        previous = Some(character);
+    }
+    if field_name.is_empty() {
+        field_name.push_str("column");
+    }
+    if field_name.starts_with(|c: char| c.is_ascii_digit()) {
+        field_name.insert(0, '_');
+    }
+    if RUST_KEYWORDS.contains(&field_name.as_str()) {
+        field_name.push('_');
+    }
+    field_name
+}
+
+/// The field name of every feature by column. Columns can map to the same name, e.g. "Fare" and
+/// "fare", in which case the later columns get a number: "fare" and "fare_2".
+pub(crate) fn get_field_names(fields: &[Field]) -> BTreeMap<String, String> {
+    let mut taken: BTreeSet<String> = BTreeSet::new();
+    let mut field_names = BTreeMap::new();
+    for field in fields {
+        let base_name = get_field_name(&field.column);
+        let mut field_name = base_name.clone();
+        let mut number = 2;
+        while taken.contains(&field_name) {
+            field_name = format!("{}_{}", base_name.trim_end_matches('_'), number);
+            number += 1;
+        }
+        taken.insert(field_name.clone());
+        field_names.insert(field.column.clone(), field_name);
+    }
+    field_names
+}
+
+/// The struct holding one row, every field is optional as a feature can be missing.
+pub(crate) fn get_row_struct(fields: &[Field], field_names: &BTreeMap<String, String>) -> String {
+    let mut row_struct = String::from("#[derive(Clone, Debug, Default)]\npub struct Row {\n");
+    for field in fields {
+        let field_name = &field_names[&field.column];
+        // A numbered name doesn't tell which column it holds:
+        if *field_name != get_field_name(&field.column) {
+            row_struct.push_str(&format!("    /// Column {:?}.\n", field.column));
+        }
+        if let Some(doc_comment) = field.field_type.get_doc_comment() {
+            row_struct.push_str(&format!("    /// {}\n", doc_comment));
+        }
+        row_struct.push_str(&format!(
+            "    pub {}: Option<{}>,\n",
+            field_name,
+            field.field_type.as_rust()
+        ));
+    }
+    row_struct.push_str("}\n");
+    row_struct
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_field_name() {
+        assert_eq!(get_field_name("PassengerId"), "passenger_id");
+        assert_eq!(get_field_name("Fare"), "fare");
+        assert_eq!(get_field_name("ticket price (EUR)"), "ticket_price_eur_");
+        assert_eq!(get_field_name("2nd class"), "_2nd_class");
+        assert_eq!(get_field_name("type"), "type_");
+        assert_eq!(get_field_name(""), "column");
+    }
+
+    #[test]
+    fn test_get_field_names_are_unique() {
+        let fields: Vec<Field> = ["Fare", "fare", "a b", "a_b", "type", "Type", "fare_2"]
+            .iter()
+            .map(|column| Field {
+                column: column.to_string(),
+                field_type: FieldType::F64,
+            })
+            .collect();
+        let field_names = get_field_names(&fields);
+        let names: Vec<&str> = fields
+            .iter()
+            .map(|field| field_names[&field.column].as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["fare", "fare_2", "a_b", "a_b_2", "type_", "type_2", "fare_2_2"]
+        );
+
+        let row_struct = get_row_struct(&fields, &field_names);
+        assert!(row_struct.contains("    /// Column \"fare\".\n    pub fare_2: Option<f64>,\n"));
+        assert!(row_struct.contains("    pub fare: Option<f64>,\n"));
+    }
+}
@@ -1 +1,11 @@
-pub const TARGET_COLUMN: &str = "TARGET_COLUMN";
+// Builds the name of an internal column, all of them share a prefix that input columns can't use:
+macro_rules! internal_column {
+    ($name:literal) => {
+        concat!("__trees_", $name)
+    };
+}
+pub(crate) use internal_column;
+
+pub const RESERVED_PREFIX: &str = internal_column!("");
+
+pub(crate) const COUNT_COL: &str = internal_column!("count");
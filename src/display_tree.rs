@@ -90,10 +90,6 @@ impl DisplayTree {
             .saturating_sub(self.display_offset.unwrap() + self.raw_display_len())
     }
 
-    fn mid_point(&self) -> usize {
-        self.display_offset.unwrap() + (self.raw_display_len() / 2)
-    }
-
     fn format_tree(&mut self) {
         self.add_missing_nodes();
         self.assign_horizontal_order();
@@ -178,7 +174,7 @@ impl DisplayTree {
 
         self.column_width = Some(max_width);
 
-        return max_width;
+        max_width
     }
 
     pub fn assign_horizontal_order(&mut self) {
@@ -279,7 +275,7 @@ impl fmt::Display for DisplayTree {
         let mut nodes = self.all_nodes();
         nodes.sort();
 
-        if let Some(first) = nodes.get(0) {
+        if let Some(first) = nodes.first() {
             write!(f, "{}", first.padded_display())?;
         }
 
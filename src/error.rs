@@ -0,0 +1,42 @@
+use polars_core::datatypes::DataType;
+use polars_core::error::PolarsError;
+use thiserror::Error;
+
+/// Everything that can go wrong while fitting or predicting with a tree.
+#[derive(Error, Debug)]
+pub enum TreeError {
+    #[error("Column {column} has unsupported data type {dtype}")]
+    UnsupportedDtype { column: String, dtype: DataType },
+    #[error("Node contains no rows")]
+    EmptyNode,
+    #[error("Target column {0} is not in the dataframe")]
+    MissingTargetColumn(String),
+    #[error("Column {0} is not in the dataframe")]
+    MissingColumn(String),
+    #[error("Target column {0} should only contain zeros and ones")]
+    NonBinaryTarget(String),
+    #[error("Column {0} starts with \"__trees_\", which is reserved for internal columns")]
+    ReservedColumnName(String),
+    #[error("Quantiles({0}) should have at least 2 bins")]
+    InvalidQuantiles(u16),
+    #[error("Subsample {0} should be above 0 and at most 1")]
+    InvalidSubsample(f64),
+    #[error("Column {0} would be overwritten by the predictions, rename it before predicting")]
+    PredictionColumnExists(String),
+    #[error("Tree has not been fitted")]
+    NotFitted,
+    #[error("Max depth: {max_depth}. Current depth: {depth}")]
+    MaxDepthExceeded { max_depth: u8, depth: u8 },
+    #[error("Invalid choice for SortType: {0}")]
+    InvalidSortType(String),
+    #[error("Invalid split selection: {0}")]
+    InvalidSplit(String),
+    #[error("Unsupported format version: {0}")]
+    UnsupportedFormatVersion(u32),
+    #[error(transparent)]
+    Polars(#[from] PolarsError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
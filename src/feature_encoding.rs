@@ -0,0 +1,224 @@
+use crate::error::TreeError;
+use crate::split_rule::Threshold;
+use polars::prelude::{col, create_enum_dtype, polars_err, Expr};
+use polars_core::datatypes::{AnyValue, DataType, TimeUnit};
+use polars_core::prelude::{NamedFrom, Series};
+use serde::{Deserialize, Serialize};
+
+/// The unit of a datetime or duration feature, the thresholds are counted in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FeatureTimeUnit {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+}
+
+impl FeatureTimeUnit {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            FeatureTimeUnit::Nanoseconds => "Nanoseconds",
+            FeatureTimeUnit::Microseconds => "Microseconds",
+            FeatureTimeUnit::Milliseconds => "Milliseconds",
+        }
+    }
+}
+
+impl From<TimeUnit> for FeatureTimeUnit {
+    fn from(time_unit: TimeUnit) -> Self {
+        match time_unit {
+            TimeUnit::Nanoseconds => FeatureTimeUnit::Nanoseconds,
+            TimeUnit::Microseconds => FeatureTimeUnit::Microseconds,
+            TimeUnit::Milliseconds => FeatureTimeUnit::Milliseconds,
+        }
+    }
+}
+
+impl From<FeatureTimeUnit> for TimeUnit {
+    fn from(time_unit: FeatureTimeUnit) -> Self {
+        match time_unit {
+            FeatureTimeUnit::Nanoseconds => TimeUnit::Nanoseconds,
+            FeatureTimeUnit::Microseconds => TimeUnit::Microseconds,
+            FeatureTimeUnit::Milliseconds => TimeUnit::Milliseconds,
+        }
+    }
+}
+
+/*
+Feature encoding governs how a feature that is neither numeric nor a string is split.
+The splits are searched on an encoded copy of the column: booleans and categoricals as strings,
+dates, datetimes and durations as their integer count of days or time units, and enums as the
+position of their category in the declared order.
+Encoding an already encoded column changes nothing, so a split rule can be applied to both.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FeatureEncoding {
+    #[default]
+    Native,
+    Boolean,
+    Categorical,
+    Date,
+    Datetime {
+        time_unit: FeatureTimeUnit,
+        time_zone: Option<String>,
+    },
+    Duration {
+        time_unit: FeatureTimeUnit,
+    },
+    Enum {
+        categories: Vec<String>,
+    },
+}
+
+impl FeatureEncoding {
+    /// The encoding of a feature of this type, `Native` for numbers, strings and everything else.
+    pub(crate) fn from_dtype(dtype: &DataType) -> Self {
+        match dtype {
+            DataType::Boolean => FeatureEncoding::Boolean,
+            DataType::Categorical(_, _) => FeatureEncoding::Categorical,
+            DataType::Date => FeatureEncoding::Date,
+            DataType::Datetime(time_unit, time_zone) => FeatureEncoding::Datetime {
+                time_unit: (*time_unit).into(),
+                time_zone: time_zone.as_ref().map(|time_zone| time_zone.to_string()),
+            },
+            DataType::Duration(time_unit) => FeatureEncoding::Duration {
+                time_unit: (*time_unit).into(),
+            },
+            DataType::Enum(Some(rev_map), _) => FeatureEncoding::Enum {
+                categories: rev_map
+                    .get_categories()
+                    .values_iter()
+                    .map(|category| category.to_string())
+                    .collect(),
+            },
+            _ => FeatureEncoding::Native,
+        }
+    }
+
+    pub(crate) fn is_native(&self) -> bool {
+        *self == FeatureEncoding::Native
+    }
+
+    /// The encoded column, values of other types or unknown categories become missing.
+    pub(crate) fn to_expr(&self, column_name: &str) -> Result<Expr, TreeError> {
+        let column = col(column_name);
+        let expr = match self {
+            FeatureEncoding::Native => column,
+            FeatureEncoding::Boolean | FeatureEncoding::Categorical => {
+                column.cast(DataType::String)
+            }
+            FeatureEncoding::Date => column.cast(DataType::Date).to_physical(),
+            FeatureEncoding::Datetime {
+                time_unit,
+                time_zone,
+            } => column
+                .cast(DataType::Datetime(
+                    (*time_unit).into(),
+                    time_zone.as_deref().map(Into::into),
+                ))
+                .to_physical(),
+            FeatureEncoding::Duration { time_unit } => column
+                .cast(DataType::Duration((*time_unit).into()))
+                .to_physical(),
+            FeatureEncoding::Enum { categories } => {
+                column.cast(get_enum_dtype(categories)?).to_physical()
+            }
+        };
+        Ok(expr)
+    }
+
+    /// Renders a threshold of an ordinal split in the type of the feature.
+    ///
+    /// Encoded values are whole numbers, so being above the threshold is being above its floor.
+    pub(crate) fn format_threshold(&self, threshold: Threshold) -> Option<String> {
+        let floor = threshold.floor();
+        let value = match self {
+            FeatureEncoding::Date => AnyValue::Date(floor as i32).to_string(),
+            FeatureEncoding::Datetime {
+                time_unit,
+                time_zone,
+            } => {
+                let time_zone = time_zone.as_deref().map(Into::into);
+                AnyValue::Datetime(floor, (*time_unit).into(), time_zone.as_ref()).to_string()
+            }
+            FeatureEncoding::Duration { time_unit } => {
+                AnyValue::Duration(floor, (*time_unit).into()).to_string()
+            }
+            FeatureEncoding::Enum { categories } => {
+                let position = floor.clamp(0, categories.len() as i64 - 1) as usize;
+                categories.get(position)?.clone()
+            }
+            FeatureEncoding::Native | FeatureEncoding::Boolean | FeatureEncoding::Categorical => {
+                return None;
+            }
+        };
+        Some(value)
+    }
+
+    /// Splits the categories of an enum into the ones below or at a threshold and the ones above.
+    pub(crate) fn split_categories(&self, threshold: Threshold) -> Option<(&[String], &[String])> {
+        match self {
+            FeatureEncoding::Enum { categories } => {
+                let n_below = threshold
+                    .floor()
+                    .saturating_add(1)
+                    .clamp(0, categories.len() as i64);
+                Some(categories.split_at(n_below as usize))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn get_enum_dtype(categories: &[String]) -> Result<DataType, TreeError> {
+    let categories = Series::new("categories".into(), categories);
+    let categories = categories
+        .str()?
+        .rechunk()
+        .downcast_iter()
+        .next()
+        .cloned()
+        .ok_or_else(|| polars_err!(ComputeError: "enum categories should fit in a single chunk"))?;
+    Ok(create_enum_dtype(categories))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+    use polars_lazy::frame::IntoLazy;
+    use std::error::Error;
+
+    #[test]
+    fn test_encoding_is_idempotent() -> Result<(), Box<dyn Error>> {
+        let encoding = FeatureEncoding::Enum {
+            categories: vec!["low".to_string(), "medium".to_string(), "high".to_string()],
+        };
+        let lf = df!("size" => ["high", "low", "unknown"])?.lazy();
+        let encoded = lf.with_column(encoding.to_expr("size")?).collect()?;
+        let positions = encoded.column("size")?.u32()?;
+        assert_eq!(positions.get(0), Some(2));
+        assert_eq!(positions.get(1), Some(0));
+        assert_eq!(positions.get(2), None);
+
+        let encoded_twice = encoded
+            .clone()
+            .lazy()
+            .with_column(encoding.to_expr("size")?)
+            .collect()?;
+        assert!(encoded.equals_missing(&encoded_twice));
+
+        assert_eq!(
+            encoding.format_threshold(Threshold::Integer(0)),
+            Some("low".to_string())
+        );
+        let (below, above) = encoding.split_categories(Threshold::Float(0.5)).unwrap();
+        assert_eq!((below.len(), above.len()), (1, 2));
+        assert_eq!(
+            FeatureEncoding::Date.format_threshold(Threshold::Integer(19723)),
+            Some("2024-01-01".to_string())
+        );
+        Ok(())
+    }
+}
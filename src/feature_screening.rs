@@ -0,0 +1,272 @@
+use crate::error::TreeError;
+use polars::prelude::{col, len, SortOptions};
+use polars_core::datatypes::DataType;
+use polars_lazy::frame::LazyFrame;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// A string column with at least this share of distinct values is mostly identifiers or free text:
+const NEAR_UNIQUE_RATIO: f64 = 0.5;
+// On fewer values or distinct values, a sorted or mostly distinct column is as likely a real
+// feature, so the automatic screening keeps it:
+const MIN_SCREENED_VALUES: u64 = 100;
+const MIN_SCREENED_DISTINCT_VALUES: u64 = 50;
+
+// Adds `include_columns` and `exclude_columns` to the impl block of a builder with a `screening`:
+macro_rules! screening_methods {
+    () => {
+        /// Only uses these columns as features, these skip the automatic screening of ID-like
+        /// columns.
+        pub fn include_columns<S: Into<String>>(
+            mut self,
+            columns: impl IntoIterator<Item = S>,
+        ) -> Self {
+            self.screening.include_columns = Some(columns.into_iter().map(Into::into).collect());
+            self
+        }
+
+        /// Never uses these columns as features.
+        pub fn exclude_columns<S: Into<String>>(
+            mut self,
+            columns: impl IntoIterator<Item = S>,
+        ) -> Self {
+            self.screening.exclude_columns = columns.into_iter().map(Into::into).collect();
+            self
+        }
+    };
+}
+pub(crate) use screening_methods;
+
+/// Why a column was left out of the features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreeningReason {
+    /// The column is in `exclude_columns`.
+    Excluded,
+    /// `include_columns` is set and doesn't contain the column.
+    NotIncluded,
+    /// An integer column without missing or repeated values, sorted in the row order.
+    IdLike,
+    /// A string column in which most values are distinct.
+    NearUnique,
+}
+
+impl fmt::Display for ScreeningReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            ScreeningReason::Excluded => "excluded by the user",
+            ScreeningReason::NotIncluded => "not in the included columns",
+            ScreeningReason::IdLike => "unique and monotonic integers, likely an identifier",
+            ScreeningReason::NearUnique => "mostly distinct strings, likely identifiers or text",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// A column that feature screening dropped before fit, together with the reason.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenedColumn {
+    pub column: String,
+    pub reason: ScreeningReason,
+}
+
+impl fmt::Display for ScreenedColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.column, self.reason)
+    }
+}
+
+/// The columns the user asked to use or leave out, the other columns are screened automatically.
+///
+/// Included columns skip the automatic screening, excluded columns are always left out. The
+/// automatic screening only looks at columns with at least 100 values, of which 50 distinct.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct FeatureScreening {
+    pub(crate) include_columns: Option<Vec<String>>,
+    pub(crate) exclude_columns: Vec<String>,
+}
+
+impl FeatureScreening {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == FeatureScreening::default()
+    }
+
+    /// The feature columns to leave out, in the order of the schema. The target is never screened.
+    pub(crate) fn screen(
+        &self,
+        lf: &LazyFrame,
+        target_column: &str,
+    ) -> Result<Vec<ScreenedColumn>, TreeError> {
+        let schema = lf.clone().collect_schema()?;
+        let listed_columns = self
+            .include_columns
+            .iter()
+            .flatten()
+            .chain(self.exclude_columns.iter());
+        for column in listed_columns {
+            if !schema.contains(column) {
+                return Err(TreeError::MissingColumn(column.to_string()));
+            }
+        }
+
+        let mut screened_columns = Vec::new();
+        for (name, dtype) in schema.iter() {
+            let column = name.as_str();
+            if column == target_column {
+                continue;
+            }
+            let is_included = self
+                .include_columns
+                .as_ref()
+                .map(|include_columns| include_columns.iter().any(|c| c == column));
+            let reason = if self.exclude_columns.iter().any(|c| c == column) {
+                Some(ScreeningReason::Excluded)
+            } else if is_included == Some(false) {
+                Some(ScreeningReason::NotIncluded)
+            } else if is_included == Some(true) {
+                None
+            } else if dtype.is_integer() && is_id_like(lf, column)? {
+                Some(ScreeningReason::IdLike)
+            } else if *dtype == DataType::String && is_near_unique(lf, column)? {
+                Some(ScreeningReason::NearUnique)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                screened_columns.push(ScreenedColumn {
+                    column: column.to_string(),
+                    reason,
+                });
+            }
+        }
+        Ok(screened_columns)
+    }
+}
+
+fn is_id_like(lf: &LazyFrame, column: &str) -> Result<bool, TreeError> {
+    let descending = SortOptions::default().with_order_descending(true);
+    let stats = lf
+        .clone()
+        .select([
+            len().cast(DataType::UInt64).alias("len"),
+            col(column)
+                .null_count()
+                .cast(DataType::UInt64)
+                .alias("nulls"),
+            col(column)
+                .n_unique()
+                .cast(DataType::UInt64)
+                .alias("unique"),
+            col(column)
+                .eq(col(column).sort(SortOptions::default()))
+                .all(true)
+                .alias("ascending"),
+            col(column)
+                .eq(col(column).sort(descending))
+                .all(true)
+                .alias("descending"),
+        ])
+        .collect()?;
+    let n_rows = stats.column("len")?.u64()?.get(0).unwrap_or_default();
+    let n_nulls = stats.column("nulls")?.u64()?.get(0).unwrap_or_default();
+    let n_unique = stats.column("unique")?.u64()?.get(0).unwrap_or_default();
+    let is_sorted = stats.column("ascending")?.bool()?.get(0) == Some(true)
+        || stats.column("descending")?.bool()?.get(0) == Some(true);
+    Ok(n_rows >= MIN_SCREENED_VALUES
+        && n_unique >= MIN_SCREENED_DISTINCT_VALUES
+        && n_nulls == 0
+        && n_unique == n_rows
+        && is_sorted)
+}
+
+fn is_near_unique(lf: &LazyFrame, column: &str) -> Result<bool, TreeError> {
+    // Missing values are neither counted as a value nor as a distinct value:
+    let stats = lf
+        .clone()
+        .select([
+            col(column).count().cast(DataType::UInt64).alias("count"),
+            col(column)
+                .drop_nulls()
+                .n_unique()
+                .cast(DataType::UInt64)
+                .alias("unique"),
+        ])
+        .collect()?;
+    let n_values = stats.column("count")?.u64()?.get(0).unwrap_or_default();
+    let n_unique = stats.column("unique")?.u64()?.get(0).unwrap_or_default();
+    Ok(n_values >= MIN_SCREENED_VALUES
+        && n_unique >= MIN_SCREENED_DISTINCT_VALUES
+        && n_unique as f64 >= NEAR_UNIQUE_RATIO * n_values as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{get_raw_test_dataframe, TITANIC_TARGET_COLUMN};
+    use polars_core::df;
+    use polars_lazy::frame::IntoLazy;
+    use std::error::Error;
+
+    #[test]
+    fn test_screen_titanic() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+        let screened = FeatureScreening::default().screen(&lf, TITANIC_TARGET_COLUMN)?;
+        let expected = [
+            ("PassengerId", ScreeningReason::IdLike),
+            ("Name", ScreeningReason::NearUnique),
+            ("Ticket", ScreeningReason::NearUnique),
+            ("Cabin", ScreeningReason::NearUnique),
+        ];
+        assert_eq!(screened.len(), expected.len());
+        for (screened_column, (column, reason)) in screened.iter().zip(expected) {
+            assert_eq!(screened_column.column, column);
+            assert_eq!(screened_column.reason, reason);
+        }
+
+        // Included columns skip the automatic screening, excluded ones are always left out:
+        let screening = FeatureScreening {
+            include_columns: Some(vec!["PassengerId".to_string(), "Sex".to_string()]),
+            exclude_columns: vec!["Sex".to_string()],
+        };
+        let screened = screening.screen(&lf, TITANIC_TARGET_COLUMN)?;
+        assert!(!screened.iter().any(|c| c.column == "PassengerId"));
+        assert!(screened.contains(&ScreenedColumn {
+            column: "Sex".to_string(),
+            reason: ScreeningReason::Excluded,
+        }));
+        assert!(screened.contains(&ScreenedColumn {
+            column: "Age".to_string(),
+            reason: ScreeningReason::NotIncluded,
+        }));
+        assert_eq!(
+            screened[0].to_string(),
+            "Pclass: not in the included columns"
+        );
+
+        let screening = FeatureScreening {
+            include_columns: None,
+            exclude_columns: vec!["Deck".to_string()],
+        };
+        assert!(matches!(
+            screening.screen(&lf, TITANIC_TARGET_COLUMN),
+            Err(TreeError::MissingColumn(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_screen_keeps_features_of_small_frames() -> Result<(), Box<dyn Error>> {
+        // Sorted and distinct years, and half distinct strings, are too few to tell apart from IDs:
+        let years: Vec<i64> = (2001..2021).collect();
+        let regions: Vec<String> = (0..20).map(|i| format!("region {}", i % 10)).collect();
+        let lf = df![
+            "Year" => years,
+            "Region" => regions,
+            TITANIC_TARGET_COLUMN => [0, 1].repeat(10),
+        ]?
+        .lazy();
+        let screened = FeatureScreening::default().screen(&lf, TITANIC_TARGET_COLUMN)?;
+        assert!(screened.is_empty());
+        Ok(())
+    }
+}
@@ -1,72 +1,54 @@
-use crate::old_preprocessing::REDUNDANT_STRING_VALUE;
+use crate::constants::COUNT_COL;
+use crate::error::TreeError;
+#[cfg(test)]
+use crate::preprocessor::Preprocessor;
 use crate::settings::Settings;
-use polars::prelude::{col, lit, when, IdxSize, JoinArgs, JoinType};
-use polars_core::datatypes::DataType;
+use polars::prelude::{col, IdxSize};
 use polars_core::prelude::SortMultipleOptions;
 use polars_lazy::frame::LazyFrame;
-use std::error::Error;
 
+// Fits and applies the preprocessing in one go, fitting keeps the preprocessor for predict instead:
+#[cfg(test)]
 pub fn rename_filler_string_full_lazyframe(
     lf: LazyFrame,
     settings: Settings,
-) -> Result<LazyFrame, Box<dyn Error>> {
-    let schema = lf.logical_plan.compute_schema()?;
-    let mut renamed_lf = lf.clone();
-    for (name, dtype) in schema.iter() {
-        if *dtype != DataType::String {
-            continue;
-        }
-        renamed_lf = rename_filler_string_single_column(renamed_lf, name, settings)?;
-    }
-    Ok(renamed_lf)
+    target_column: &str,
+) -> Result<LazyFrame, TreeError> {
+    let preprocessor = Preprocessor::fit(&lf, settings, Vec::new(), target_column)?;
+    preprocessor.transform(lf)
 }
 
-fn rename_filler_string_single_column(
-    lf: LazyFrame,
+/// Gathers the strings that are prominent enough to keep, most common first.
+///
+/// None if no string is prominent, in which case the column is dropped.
+pub(crate) fn get_prominent_strings(
+    lf: &LazyFrame,
     column_name: &str,
     settings: Settings,
-) -> Result<LazyFrame, Box<dyn Error>> {
-    // Temporary columns:
-    let top_n_column = "is_top_5";
-    let count_column = "count";
-
-    // Gather all strings that are prominent enough to keep:
+) -> Result<Option<Vec<String>>, TreeError> {
     let top_strings = lf
         .clone()
         .group_by([col(column_name)])
-        .agg([col(column_name).count().alias(count_column)])
+        .agg([col(column_name).count().alias(COUNT_COL)])
         .sort(
-            [count_column],
+            [COUNT_COL],
             SortMultipleOptions::default().with_order_descending(true),
         )
-        .filter(col(count_column).gt_eq(settings.get_min_leave_size() as i32))
+        .filter(col(COUNT_COL).gt_eq(settings.get_min_leave_size() as i32))
         .select([col(column_name)])
         .limit(settings.get_max_cardinality() as IdxSize)
-        .with_column(lit(true).alias(top_n_column));
+        .collect()?;
 
-    // If top_strings is empty, drop column:
-    let n_rows = top_strings.clone().collect()?.height();
-    if n_rows == 0 {
-        return Ok(lf.drop([column_name]));
+    if top_strings.height() == 0 {
+        return Ok(None);
     }
-
-    // This join is "null" where the string value is not prominent:
-    let join_lf = lf.join(
-        top_strings,
-        [col(column_name)],
-        [col(column_name)],
-        JoinArgs::new(JoinType::Left),
-    );
-
-    // Rename "null" with filler string:
-    let renamed_lf = join_lf
-        .with_column(
-            when(col(top_n_column).is_null())
-                .then(lit(REDUNDANT_STRING_VALUE))
-                .otherwise(col(column_name))
-                .alias(column_name),
-        )
-        .drop([top_n_column]);
-
-    Ok(renamed_lf)
+    // Missing values can be prominent as well, they stay missing and need no entry:
+    let prominent_strings = top_strings
+        .column(column_name)?
+        .str()?
+        .into_iter()
+        .flatten()
+        .map(|value| value.to_string())
+        .collect();
+    Ok(Some(prominent_strings))
 }
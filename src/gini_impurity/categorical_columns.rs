@@ -1,64 +1,327 @@
-use crate::constants::TARGET_COLUMN;
-use crate::gini_impurity::constants::{COUNT_LEFT_COL, COUNT_RIGHT_COL, SELECTION_COLUMN};
+use crate::error::TreeError;
+use crate::gini_impurity::constants::{
+    CANDIDATE_COLUMN, COUNT_LEFT_COL, COUNT_RIGHT_COL, FEATURE_COLUMN_NAME, LEFT_GROUP_FLAG_COL,
+    SELECTION_COLUMN, SORT_TYPE_COL, TOTAL_PER_TARGET_COL,
+};
 use crate::gini_impurity::gini_impurity::{
-    add_totals_of_in_out_group, add_zero_count, compute_gini_per_feature, extract_best_feature,
-    get_optimal_gini_impurity_for_column, normalize_gini_per_group, pre_process_for_gini,
+    add_missing_direction, add_totals_of_in_out_group, add_zero_count, compute_gini_per_feature,
+    extract_best_feature, normalize_gini_per_group, pre_process_for_gini,
 };
 use crate::gini_impurity::sort_type::SortType;
 use crate::old_preprocessing::REDUNDANT_STRING_VALUE;
-use crate::test_utils::assert_single_row_df_equal;
+use crate::settings::{CategoricalSearch, Settings, SplitCriterion};
 use polars::prelude::{col, lit};
 use polars_core::datatypes::DataType;
 use polars_core::df;
-use polars_lazy::frame::LazyFrame;
+use polars_core::frame::DataFrame;
+use polars_core::prelude::{NamedFrom, Series, SortMultipleOptions, UniqueKeepStrategy};
+use polars_lazy::frame::{IntoLazy, LazyFrame};
 
-pub fn group_by_for_gini_impurity_categorical(lf: &LazyFrame) -> LazyFrame {
+pub fn group_by_for_gini_impurity_categorical(lf: &LazyFrame, target_column: &str) -> LazyFrame {
     // Instead of grouping by feature column, should group by selection column.
     let mut grouped_lf = lf
-        // Group in and out:
+        // Group in and out, missing values are added to either group later on:
         .clone()
+        .filter(col(SELECTION_COLUMN).is_not_null())
         .group_by([col("*")])
-        .agg([col(TARGET_COLUMN)
+        .agg([col(target_column)
             .count()
             .alias(COUNT_LEFT_COL)
             .cast(DataType::Float64)]);
 
-    grouped_lf = add_zero_count(SELECTION_COLUMN, TARGET_COLUMN, COUNT_LEFT_COL, &grouped_lf);
+    grouped_lf = add_zero_count(SELECTION_COLUMN, target_column, COUNT_LEFT_COL, &grouped_lf);
 
     grouped_lf = grouped_lf
         .with_columns([col(COUNT_LEFT_COL)
             .sum()
-            .over([col(TARGET_COLUMN)])
-            .alias("total_per_target")])
-        .with_columns([(col("total_per_target") - col(COUNT_LEFT_COL)).alias(COUNT_RIGHT_COL)])
+            .over([col(target_column)])
+            .alias(TOTAL_PER_TARGET_COL)])
+        .with_columns([(col(TOTAL_PER_TARGET_COL) - col(COUNT_LEFT_COL)).alias(COUNT_RIGHT_COL)])
         .filter(col(SELECTION_COLUMN).neq(lit(REDUNDANT_STRING_VALUE)))
-        .drop(["total_per_target"])
-        .sort([SELECTION_COLUMN, TARGET_COLUMN], Default::default());
+        .drop([TOTAL_PER_TARGET_COL])
+        .sort([SELECTION_COLUMN, target_column], Default::default());
     grouped_lf
 }
 
+pub(crate) fn get_categorical_candidate_splits(lf: &LazyFrame) -> LazyFrame {
+    // Every prominent category is a candidate for the left group:
+    let candidates = lf
+        .clone()
+        .select([col(SELECTION_COLUMN).alias(CANDIDATE_COLUMN)])
+        .unique(None, UniqueKeepStrategy::Any)
+        .filter(col(CANDIDATE_COLUMN).neq(lit(REDUNDANT_STRING_VALUE)));
+
+    // Pair every row with every candidate and flag the rows that fall in the left group:
+    lf.clone()
+        .cross_join(candidates, None)
+        .with_column(
+            col(SELECTION_COLUMN)
+                .eq(col(CANDIDATE_COLUMN))
+                .alias(LEFT_GROUP_FLAG_COL),
+        )
+        .drop([SELECTION_COLUMN])
+        .rename([CANDIDATE_COLUMN], [SELECTION_COLUMN], true)
+}
+
 pub fn get_optimal_gini_impurity_for_categorical_column(
     lf: &LazyFrame,
     feature_column: &str,
-) -> LazyFrame {
-    let lf = pre_process_for_gini(&lf, SortType::Categorical, feature_column);
-    let mut grouped_lf = group_by_for_gini_impurity_categorical(&lf);
-    grouped_lf = add_totals_of_in_out_group(&grouped_lf);
-    let gini_lf = compute_gini_per_feature(&grouped_lf);
+    target_column: &str,
+    settings: Settings,
+) -> Result<LazyFrame, TreeError> {
+    let lf = pre_process_for_gini(lf, SortType::Categorical, feature_column, target_column);
+    match settings.get_categorical_search() {
+        CategoricalSearch::OneVsRest => get_best_split_of_grouped(
+            &group_by_for_gini_impurity_categorical(&lf, target_column),
+            &lf,
+            target_column,
+            settings.get_split_criterion(),
+        ),
+        CategoricalSearch::Subsets => get_best_category_subset(
+            &lf,
+            feature_column,
+            target_column,
+            settings.get_split_criterion(),
+        ),
+    }
+}
+
+fn get_best_split_of_grouped(
+    grouped_lf: &LazyFrame,
+    lf: &LazyFrame,
+    target_column: &str,
+    criterion: SplitCriterion,
+) -> Result<LazyFrame, TreeError> {
+    let grouped_lf = add_missing_direction(grouped_lf, lf, SELECTION_COLUMN, target_column)?;
+    let grouped_lf = add_totals_of_in_out_group(&grouped_lf);
+    let gini_lf = compute_gini_per_feature(&grouped_lf, criterion);
     let normalized_gini_lf = normalize_gini_per_group(&grouped_lf, &gini_lf);
-    let final_lf = extract_best_feature(normalized_gini_lf);
-    final_lf
+    // The categories are kept as strings:
+    Ok(extract_best_feature(
+        normalized_gini_lf,
+        SortType::Categorical,
+        &DataType::String,
+    ))
+}
+
+/// Counts of every target class per category, the filler string is kept apart as it never
+/// joins the left group.
+struct CategoryCounts {
+    categories: Vec<String>,
+    classes: Vec<String>,
+    counts: Vec<Vec<f64>>,
+    totals: Vec<f64>,
+}
+
+impl CategoryCounts {
+    fn from_lazyframe(lf: &LazyFrame, target_column: &str) -> Result<Self, TreeError> {
+        let counts_df = lf
+            .clone()
+            .filter(col(SELECTION_COLUMN).is_not_null())
+            .group_by([
+                col(SELECTION_COLUMN),
+                col(target_column).cast(DataType::String),
+            ])
+            .agg([col(target_column)
+                .count()
+                .alias(COUNT_LEFT_COL)
+                .cast(DataType::Float64)])
+            .sort(
+                [SELECTION_COLUMN, target_column],
+                SortMultipleOptions::default(),
+            )
+            .collect()?;
+
+        let rows: Vec<(&str, &str, f64)> = counts_df
+            .column(SELECTION_COLUMN)?
+            .str()?
+            .into_no_null_iter()
+            .zip(counts_df.column(target_column)?.str()?.into_no_null_iter())
+            .zip(counts_df.column(COUNT_LEFT_COL)?.f64()?.into_no_null_iter())
+            .map(|((category, class), count)| (category, class, count))
+            .collect();
+
+        let mut categories: Vec<String> = Vec::new();
+        let mut classes: Vec<String> = Vec::new();
+        for (category, class, _) in rows.iter() {
+            if *category != REDUNDANT_STRING_VALUE && !categories.iter().any(|c| c == category) {
+                categories.push(category.to_string());
+            }
+            if !classes.iter().any(|c| c == class) {
+                classes.push(class.to_string());
+            }
+        }
+        classes.sort();
+
+        let mut counts = vec![vec![0.0; classes.len()]; categories.len()];
+        let mut totals = vec![0.0; classes.len()];
+        for (category, class, count) in rows {
+            let class_index = classes.iter().position(|c| c == class).unwrap();
+            totals[class_index] += count;
+            if let Some(category_index) = categories.iter().position(|c| c == category) {
+                counts[category_index][class_index] += count;
+            }
+        }
+
+        Ok(Self {
+            categories,
+            classes,
+            counts,
+            totals,
+        })
+    }
+
+    fn count_left(&self, subset: &[usize]) -> Vec<f64> {
+        (0..self.classes.len())
+            .map(|class_index| {
+                subset
+                    .iter()
+                    .map(|category_index| self.counts[*category_index][class_index])
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn is_valid_split(&self, subset: &[usize]) -> bool {
+        // Both groups need at least one row:
+        let total_left: f64 = self.count_left(subset).iter().sum();
+        let total: f64 = self.totals.iter().sum();
+        total_left > 0.0 && total_left < total
+    }
+
+    fn get_categories(&self, subset: &[usize]) -> Vec<&str> {
+        let mut categories: Vec<&str> = subset
+            .iter()
+            .map(|index| self.categories[*index].as_str())
+            .collect();
+        categories.sort();
+        categories
+    }
+
+    fn to_grouped_lazyframe(
+        &self,
+        subsets: &[Vec<usize>],
+        feature_column: &str,
+        target_column: &str,
+    ) -> Result<LazyFrame, TreeError> {
+        // Same layout as the grouped lazyframe of a one-vs-rest split, a subset is selected by its
+        // position, so that the categories are never joined into a single string:
+        let mut selections: Vec<String> = Vec::new();
+        let mut targets: Vec<String> = Vec::new();
+        let mut counts_left: Vec<f64> = Vec::new();
+        let mut counts_right: Vec<f64> = Vec::new();
+        for (subset_index, subset) in subsets.iter().enumerate() {
+            let count_left = self.count_left(subset);
+            for (class_index, class) in self.classes.iter().enumerate() {
+                selections.push(subset_index.to_string());
+                targets.push(class.clone());
+                counts_left.push(count_left[class_index]);
+                counts_right.push(self.totals[class_index] - count_left[class_index]);
+            }
+        }
+        let n_rows = selections.len();
+        let grouped_df = df![
+            SELECTION_COLUMN => selections,
+            FEATURE_COLUMN_NAME => vec![feature_column; n_rows],
+            SORT_TYPE_COL => vec![SortType::Categorical.as_str(); n_rows],
+            target_column => targets,
+            COUNT_LEFT_COL => counts_left,
+            COUNT_RIGHT_COL => counts_right,
+        ]?;
+        Ok(grouped_df.lazy())
+    }
+}
+
+// Position of the best subset, as selected in the grouped lazyframe of the subsets:
+fn get_best_subset_index(best_df: &DataFrame) -> Result<usize, TreeError> {
+    let selection = best_df
+        .column(SELECTION_COLUMN)?
+        .list()?
+        .get_as_series(0)
+        .ok_or(TreeError::EmptyNode)?;
+    let subset_index = selection.str()?.get(0).ok_or(TreeError::EmptyNode)?;
+    subset_index
+        .parse()
+        .map_err(|_| TreeError::InvalidSplit(subset_index.to_string()))
+}
+
+fn get_best_category_subset(
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+    criterion: SplitCriterion,
+) -> Result<LazyFrame, TreeError> {
+    let category_counts = CategoryCounts::from_lazyframe(lf, target_column)?;
+    let n_categories = category_counts.categories.len();
+
+    let subsets: Vec<Vec<usize>> = if category_counts.classes.len() <= 2 {
+        // For a binary target, the best subset is a prefix of the categories sorted by the rate
+        // of one of the classes:
+        let mut ordered: Vec<usize> = (0..n_categories).collect();
+        let rate = |index: &usize| {
+            let counts = &category_counts.counts[*index];
+            counts[counts.len() - 1] / counts.iter().sum::<f64>()
+        };
+        ordered.sort_by(|a, b| rate(a).total_cmp(&rate(b)));
+        (1..=n_categories)
+            .map(|size| ordered[..size].to_vec())
+            .filter(|subset| category_counts.is_valid_split(subset))
+            .collect()
+    } else {
+        // For a multiclass target, greedily grow the left group by the category that improves
+        // the split the most. This takes at most one step per category:
+        let mut subsets: Vec<Vec<usize>> = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+        while current.len() < n_categories {
+            let step_subsets: Vec<Vec<usize>> = (0..n_categories)
+                .filter(|index| !current.contains(index))
+                .map(|index| [current.clone(), vec![index]].concat())
+                .filter(|subset| category_counts.is_valid_split(subset))
+                .collect();
+            if step_subsets.is_empty() {
+                break;
+            }
+            let step_lf = category_counts.to_grouped_lazyframe(
+                &step_subsets,
+                feature_column,
+                target_column,
+            )?;
+            let best_df =
+                get_best_split_of_grouped(&step_lf, lf, target_column, criterion)?.collect()?;
+            current = step_subsets[get_best_subset_index(&best_df)?].clone();
+            subsets.push(current.clone());
+        }
+        subsets
+    };
+
+    let grouped_lf =
+        category_counts.to_grouped_lazyframe(&subsets, feature_column, target_column)?;
+    let mut best_df =
+        get_best_split_of_grouped(&grouped_lf, lf, target_column, criterion)?.collect()?;
+    if best_df.height() == 0 {
+        return Ok(best_df.lazy());
+    }
+    // The position of the best subset is replaced by its categories:
+    let categories = category_counts.get_categories(&subsets[get_best_subset_index(&best_df)?]);
+    best_df.with_column(Series::new(
+        SELECTION_COLUMN.into(),
+        [Series::new("".into(), categories)],
+    ))?;
+    Ok(best_df.lazy())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::constants::TARGET_COLUMN;
     use crate::gini_impurity::constants::{
-        FEATURE_COLUMN_NAME, NORMALIZED_CHILD_GINI, SELECTION_COLUMN, SORT_TYPE_COL,
-        TOTAL_LEFT_GROUP_COL, TOTAL_RIGHT_GROUP_COL,
+        FEATURE_COLUMN_NAME, MISSING_LEFT_COL, NORMALIZED_CHILD_GINI, SELECTION_COLUMN,
+        SORT_TYPE_COL, SPLIT_INTEGER_THRESHOLD_COL, SPLIT_THRESHOLD_COL,
+        SPLIT_UNSIGNED_THRESHOLD_COL, TOTAL_LEFT_GROUP_COL, TOTAL_RIGHT_GROUP_COL,
     };
-    use crate::test_utils::get_preprocessed_test_dataframe;
+    use crate::gini_impurity::gini_impurity::get_optimal_gini_impurity_for_column;
+    use crate::test_utils::assert_single_row_df_equal;
+    use crate::test_utils::{get_preprocessed_test_dataframe, TITANIC_TARGET_COLUMN};
+    use polars_core::df;
     #[test]
     fn debug() {
         unsafe {
@@ -66,15 +329,16 @@ mod tests {
         }
 
         let mut lf = get_preprocessed_test_dataframe();
-        lf = lf.drop([TARGET_COLUMN]);
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
         let feature_column = "Embarked";
         let target_column = "Pclass";
-        lf = lf.rename([target_column], [TARGET_COLUMN], true);
 
-        let lf = pre_process_for_gini(&lf, SortType::Categorical, feature_column);
-        let mut grouped_lf = group_by_for_gini_impurity_categorical(&lf);
+        let lf = pre_process_for_gini(&lf, SortType::Categorical, feature_column, target_column);
+        let mut grouped_lf = group_by_for_gini_impurity_categorical(&lf, target_column);
+        grouped_lf =
+            add_missing_direction(&grouped_lf, &lf, SELECTION_COLUMN, target_column).unwrap();
         grouped_lf = add_totals_of_in_out_group(&grouped_lf);
-        let gini_lf = compute_gini_per_feature(&grouped_lf);
+        let gini_lf = compute_gini_per_feature(&grouped_lf, SplitCriterion::Gini);
         let normalized_gini_lf = normalize_gini_per_group(&grouped_lf, &gini_lf);
 
         println!("{:?}", normalized_gini_lf.collect());
@@ -87,23 +351,31 @@ mod tests {
         }
 
         let mut lf = get_preprocessed_test_dataframe();
-        lf = lf.drop([TARGET_COLUMN]);
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
         let feature_column = "Embarked";
         let target_column = "Pclass";
-        lf = lf.rename([target_column], [TARGET_COLUMN], true);
 
         // END OF PRE-PROCESSING, start of Gini computation:
-        let final_lf =
-            get_optimal_gini_impurity_for_column(&lf, feature_column, SortType::Categorical);
+        let final_lf = get_optimal_gini_impurity_for_column(
+            &lf,
+            feature_column,
+            target_column,
+            SortType::Categorical,
+            Settings::default(),
+        )?;
         let collected = final_lf.collect()?;
 
         let expected_df = df![
             FEATURE_COLUMN_NAME => &["Embarked"],
             SORT_TYPE_COL => &["categorical"],
-            SELECTION_COLUMN => &["C"],
-            NORMALIZED_CHILD_GINI => &[0.57038_f64],
-            TOTAL_LEFT_GROUP_COL => &[168.0],
-            TOTAL_RIGHT_GROUP_COL => &[723.0],
+            SPLIT_THRESHOLD_COL => &[None::<f64>],
+            SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            SELECTION_COLUMN => [Series::new("".into(), ["C"])],
+            MISSING_LEFT_COL => &[true],
+            NORMALIZED_CHILD_GINI => &[0.568879_f64],
+            TOTAL_LEFT_GROUP_COL => &[170.0],
+            TOTAL_RIGHT_GROUP_COL => &[721.0],
         ]?;
 
         assert_eq!(collected.schema(), expected_df.schema());
@@ -111,4 +383,111 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_entropy_and_log_loss_for_categorical_column() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let mut lf = get_preprocessed_test_dataframe();
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
+        let feature_column = "Embarked";
+        let target_column = "Pclass";
+
+        for (criterion, expected_impurity) in [
+            (SplitCriterion::Entropy, 1.377526_f64),
+            (SplitCriterion::LogLoss, 0.954828_f64),
+        ] {
+            let mut settings = Settings::default();
+            settings.set_split_criterion(criterion);
+            let collected = get_optimal_gini_impurity_for_column(
+                &lf,
+                feature_column,
+                target_column,
+                SortType::Categorical,
+                settings,
+            )?
+            .collect()?;
+
+            let expected_df = df![
+                FEATURE_COLUMN_NAME => &["Embarked"],
+                SORT_TYPE_COL => &["categorical"],
+                SPLIT_THRESHOLD_COL => &[None::<f64>],
+                SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+                SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+                SELECTION_COLUMN => [Series::new("".into(), ["C"])],
+                MISSING_LEFT_COL => &[true],
+                NORMALIZED_CHILD_GINI => &[expected_impurity],
+                TOTAL_LEFT_GROUP_COL => &[170.0],
+                TOTAL_RIGHT_GROUP_COL => &[721.0],
+            ]?;
+            assert_single_row_df_equal(&collected, &expected_df)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subset_split_for_binary_target() -> Result<(), Box<dyn std::error::Error>> {
+        let lf = get_preprocessed_test_dataframe();
+        let feature_column = "Embarked";
+        let target_column = TITANIC_TARGET_COLUMN;
+
+        let mut settings = Settings::default();
+        settings.set_categorical_search(CategoricalSearch::Subsets);
+        let collected = get_optimal_gini_impurity_for_column(
+            &lf,
+            feature_column,
+            target_column,
+            SortType::Categorical,
+            settings,
+        )?
+        .collect()?;
+
+        // Grouping "Q" with "S" beats any single port:
+        let expected_df = df![
+            FEATURE_COLUMN_NAME => &["Embarked"],
+            SORT_TYPE_COL => &["categorical"],
+            SPLIT_THRESHOLD_COL => &[None::<f64>],
+            SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            SELECTION_COLUMN => [Series::new("".into(), ["Q", "S"])],
+            MISSING_LEFT_COL => &[false],
+            NORMALIZED_CHILD_GINI => &[0.458574_f64],
+            TOTAL_LEFT_GROUP_COL => &[721.0],
+            TOTAL_RIGHT_GROUP_COL => &[170.0],
+        ]?;
+        assert_single_row_df_equal(&collected, &expected_df)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_subset_split_for_multiclass_target() -> Result<(), Box<dyn std::error::Error>> {
+        let mut lf = get_preprocessed_test_dataframe();
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
+        let feature_column = "Embarked";
+        let target_column = "Pclass";
+
+        let get_impurity = |settings: Settings| -> Result<f64, Box<dyn std::error::Error>> {
+            let collected = get_optimal_gini_impurity_for_column(
+                &lf,
+                feature_column,
+                target_column,
+                SortType::Categorical,
+                settings,
+            )?
+            .collect()?;
+            Ok(collected
+                .column(NORMALIZED_CHILD_GINI)?
+                .f64()?
+                .get(0)
+                .unwrap())
+        };
+
+        // The greedy search starts from the best single category, so it is never worse:
+        let mut settings = Settings::default();
+        let one_vs_rest_impurity = get_impurity(settings)?;
+        settings.set_categorical_search(CategoricalSearch::Subsets);
+        let subset_impurity = get_impurity(settings)?;
+        assert!(subset_impurity <= one_vs_rest_impurity + 0.00001);
+        Ok(())
+    }
 }
@@ -1,12 +1,36 @@
-pub const QUANTILES: [f64; 8] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.9];
-pub const COUNT_LEFT_COL: &str = "count_LEFT";
-pub const COUNT_RIGHT_COL: &str = "count_RIGHT";
-pub const GINI_IMPURITY_LEFT_GROUP_COL: &str = "gini_LEFT";
-pub const GINI_IMPURITY_RIGHT_GROUP_COL: &str = "gini_RIGHT";
-pub const TOTAL_LEFT_GROUP_COL: &str = "total_LEFT_group";
-pub const TOTAL_RIGHT_GROUP_COL: &str = "total_RIGHT_group";
-pub const FEATURE_COLUMN_NAME: &str = "FEATURE_COLUMN_NAME";
-pub const SORT_TYPE_COL: &str = "SORT_TYPE";
-pub const TEMP_COLUMN_ORDINAL: &str = "temp_ordinal";
-pub const SELECTION_COLUMN: &str = "SELECTION_COLUMN";
-pub(crate) const NORMALIZED_CHILD_GINI: &str = "NORMALIZED_CHILD_GINI";
+use crate::constants::internal_column;
+
+pub const COUNT_LEFT_COL: &str = internal_column!("count_left");
+pub const COUNT_RIGHT_COL: &str = internal_column!("count_right");
+pub const GINI_IMPURITY_LEFT_GROUP_COL: &str = internal_column!("gini_left");
+pub const GINI_IMPURITY_RIGHT_GROUP_COL: &str = internal_column!("gini_right");
+pub const TOTAL_LEFT_GROUP_COL: &str = internal_column!("total_left");
+pub const TOTAL_RIGHT_GROUP_COL: &str = internal_column!("total_right");
+pub const FEATURE_COLUMN_NAME: &str = internal_column!("feature_column_name");
+pub const SORT_TYPE_COL: &str = internal_column!("sort_type");
+pub const TEMP_COLUMN_ORDINAL: &str = internal_column!("temp_ordinal");
+pub const THRESHOLD_COLUMN: &str = internal_column!("temp_threshold");
+pub const SELECTION_COLUMN: &str = internal_column!("selection_column");
+pub const SPLIT_THRESHOLD_COL: &str = internal_column!("split_threshold");
+// Integer features keep their thresholds exact, in a column of their own:
+pub const SPLIT_INTEGER_THRESHOLD_COL: &str = internal_column!("split_integer_threshold");
+pub const SPLIT_UNSIGNED_THRESHOLD_COL: &str = internal_column!("split_unsigned_threshold");
+pub const MISSING_LEFT_COL: &str = internal_column!("missing_left");
+pub const MISSING_COUNT_COL: &str = internal_column!("count_missing");
+// The sums of a numeric target and of its squares, for the cumulative squared error pass:
+pub const SUM_LEFT_COL: &str = internal_column!("sum_left");
+pub const SUM_RIGHT_COL: &str = internal_column!("sum_right");
+pub const MISSING_SUM_COL: &str = internal_column!("sum_missing");
+pub const SQUARES_LEFT_COL: &str = internal_column!("squares_left");
+pub const SQUARES_RIGHT_COL: &str = internal_column!("squares_right");
+pub const MISSING_SQUARES_COL: &str = internal_column!("squares_missing");
+pub(crate) const NORMALIZED_CHILD_GINI: &str = internal_column!("normalized_child_gini");
+pub const LEFT_GROUP_FLAG_COL: &str = internal_column!("is_left_group");
+pub const CANDIDATE_COLUMN: &str = internal_column!("candidate_column");
+// Position of a category sorted by its mean or median target, for the regression subset search:
+pub const CATEGORY_RANK_COL: &str = internal_column!("category_rank");
+pub const IMPURITY_LEFT_GROUP_COL: &str = internal_column!("impurity_left");
+pub const IMPURITY_RIGHT_GROUP_COL: &str = internal_column!("impurity_right");
+pub const GROUP_COUNT_COL: &str = internal_column!("group_count");
+pub const GROUP_IMPURITY_COL: &str = internal_column!("group_impurity");
+pub const TOTAL_PER_TARGET_COL: &str = internal_column!("total_per_target");
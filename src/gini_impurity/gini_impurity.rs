@@ -1,18 +1,21 @@
-use crate::constants::TARGET_COLUMN;
+use crate::error::TreeError;
 use crate::gini_impurity::constants::{
     COUNT_LEFT_COL, COUNT_RIGHT_COL, FEATURE_COLUMN_NAME, GINI_IMPURITY_LEFT_GROUP_COL,
-    GINI_IMPURITY_RIGHT_GROUP_COL, NORMALIZED_CHILD_GINI, SELECTION_COLUMN, SORT_TYPE_COL,
-    TOTAL_LEFT_GROUP_COL, TOTAL_RIGHT_GROUP_COL,
+    GINI_IMPURITY_RIGHT_GROUP_COL, MISSING_COUNT_COL, MISSING_LEFT_COL, NORMALIZED_CHILD_GINI,
+    SELECTION_COLUMN, SORT_TYPE_COL, SPLIT_INTEGER_THRESHOLD_COL, SPLIT_THRESHOLD_COL,
+    SPLIT_UNSIGNED_THRESHOLD_COL, TOTAL_LEFT_GROUP_COL, TOTAL_RIGHT_GROUP_COL,
 };
 use crate::gini_impurity::sort_type::{get_sort_type_for_dtype, SortType};
-use crate::gini_impurity::{categorical_columns, ordinal_columns, sort_type};
-use polars::prelude::{col, lit, JoinArgs, JoinType, UnionArgs};
+use crate::gini_impurity::{categorical_columns, ordinal_columns};
+use crate::settings::{Settings, SplitCriterion};
+use polars::prelude::{col, lit, when, Expr, JoinArgs, JoinType, UnionArgs, NULL};
+use polars_core::datatypes::{DataType, PlSmallStr};
 use polars_core::prelude::{SortMultipleOptions, UniqueKeepStrategy};
+use polars_core::schema::Schema;
 use polars_lazy::frame::LazyFrame;
 use polars_lazy::prelude::concat;
-use std::error::Error;
-
-// TODO: Add fail safe to ensure TARGET_COLUMN doesn't already exist in dataframe
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
 
 pub(crate) fn add_zero_count(
     feature_column: &str,
@@ -22,7 +25,7 @@ pub(crate) fn add_zero_count(
 ) -> LazyFrame {
     // Adds a zero if a combination of feature and target doesn't exist for a certain group_by.
     // Get unique combinations of feature and target:
-    let combinations = get_unique_combinations(feature_column, target_column, &lf);
+    let combinations = get_unique_combinations(feature_column, target_column, lf);
 
     // Add back the count column and fill with zeros:
     let combinations_with_count_lf = combinations
@@ -41,41 +44,48 @@ pub(crate) fn add_zero_count(
         .select([col("*").exclude([feature_column, count_column])])
         .unique(None, UniqueKeepStrategy::Any);
 
-    let full_lf = combinations_with_count_lf.join(
+    combinations_with_count_lf.join(
         static_lf,
         &[col(target_column)],
         &[col(target_column)],
         JoinArgs::new(JoinType::Left),
-    );
-    full_lf
+    )
 }
 
 pub(crate) fn get_optimal_gini_impurity_for_column(
     lf: &LazyFrame,
     feature_column: &str,
+    target_column: &str,
     sort_type: SortType,
-) -> LazyFrame {
-    let lf_output: LazyFrame;
+    settings: Settings,
+) -> Result<LazyFrame, TreeError> {
     match sort_type {
-        SortType::Ordinal => {
-            lf_output =
-                ordinal_columns::get_optimal_gini_impurity_for_ordinal_column(lf, feature_column);
-        }
+        SortType::Ordinal => Ok(
+            ordinal_columns::get_optimal_gini_impurity_for_ordinal_column(
+                lf,
+                feature_column,
+                target_column,
+                settings,
+            )?,
+        ),
         SortType::Categorical => {
-            lf_output = categorical_columns::get_optimal_gini_impurity_for_categorical_column(
+            categorical_columns::get_optimal_gini_impurity_for_categorical_column(
                 lf,
                 feature_column,
-            );
+                target_column,
+                settings,
+            )
         }
     }
-    lf_output
 }
 
+// The normalization is a weighted average of the impurity of both groups, and is therefore
+// independent of the split criterion that was used to compute the impurity of each group.
 pub(crate) fn normalize_gini_per_group(grouped_lf: &LazyFrame, gini_lf: &LazyFrame) -> LazyFrame {
     let mut normalized_lf = grouped_lf.clone().join(
         gini_lf.clone(),
-        [col(SELECTION_COLUMN)],
-        [col(SELECTION_COLUMN)],
+        [col(SELECTION_COLUMN), col(MISSING_LEFT_COL)],
+        [col(SELECTION_COLUMN), col(MISSING_LEFT_COL)],
         JoinArgs::new(JoinType::Left),
     );
 
@@ -100,31 +110,53 @@ pub(crate) fn normalize_gini_per_group(grouped_lf: &LazyFrame, gini_lf: &LazyFra
     normalized_lf
 }
 
-pub(crate) fn compute_gini_per_feature(grouped_lf: &LazyFrame) -> LazyFrame {
+pub(crate) fn get_impurity_term(
+    count_column: &str,
+    total_column: &str,
+    criterion: SplitCriterion,
+) -> Expr {
+    // The impurity of a group is the sum of this term over all target values:
+    let proportion = col(count_column) / col(total_column);
+    match criterion {
+        // 1 - sum(p^2) == sum(p * (1 - p)):
+        SplitCriterion::Gini => proportion.clone() * (lit(1.0) - proportion),
+        SplitCriterion::Entropy => get_entropy_term(proportion, 2.0),
+        SplitCriterion::LogLoss => get_entropy_term(proportion, std::f64::consts::E),
+    }
+}
+
+fn get_entropy_term(proportion: Expr, base: f64) -> Expr {
+    // -sum(p * log(p)), where a target value that is absent contributes zero:
+    when(proportion.clone().gt(lit(0.0)))
+        .then(lit(0.0) - proportion.clone() * proportion.log(base))
+        .otherwise(lit(0.0))
+}
+
+pub(crate) fn compute_gini_per_feature(
+    grouped_lf: &LazyFrame,
+    criterion: SplitCriterion,
+) -> LazyFrame {
     let mut gini_lf = grouped_lf.clone().with_column(
-        ((col(COUNT_LEFT_COL) / col(TOTAL_LEFT_GROUP_COL)).pow(lit(2.0)))
+        get_impurity_term(COUNT_LEFT_COL, TOTAL_LEFT_GROUP_COL, criterion)
             .alias(GINI_IMPURITY_LEFT_GROUP_COL),
     );
 
     gini_lf = gini_lf.with_column(
-        ((col(COUNT_RIGHT_COL) / col(TOTAL_RIGHT_GROUP_COL)).pow(lit(2.0)))
+        get_impurity_term(COUNT_RIGHT_COL, TOTAL_RIGHT_GROUP_COL, criterion)
             .alias(GINI_IMPURITY_RIGHT_GROUP_COL),
     );
 
     gini_lf = gini_lf
         .select([
             col(SELECTION_COLUMN),
+            col(MISSING_LEFT_COL),
             col(GINI_IMPURITY_LEFT_GROUP_COL),
             col(GINI_IMPURITY_RIGHT_GROUP_COL),
         ])
-        .group_by([col(SELECTION_COLUMN)])
+        .group_by([col(SELECTION_COLUMN), col(MISSING_LEFT_COL)])
         .agg([
             col(GINI_IMPURITY_LEFT_GROUP_COL).sum(),
             col(GINI_IMPURITY_RIGHT_GROUP_COL).sum(),
-        ])
-        .with_columns([
-            (lit(1.0) - col(GINI_IMPURITY_LEFT_GROUP_COL)).alias(GINI_IMPURITY_LEFT_GROUP_COL),
-            (lit(1.0) - col(GINI_IMPURITY_RIGHT_GROUP_COL)).alias(GINI_IMPURITY_RIGHT_GROUP_COL),
         ]);
     gini_lf
 }
@@ -132,39 +164,80 @@ pub(crate) fn compute_gini_per_feature(grouped_lf: &LazyFrame) -> LazyFrame {
 pub(crate) fn add_totals_of_in_out_group(grouped_lf: &LazyFrame) -> LazyFrame {
     let in_group_lf = grouped_lf
         .clone()
-        .group_by([col(SELECTION_COLUMN)])
+        .group_by([col(SELECTION_COLUMN), col(MISSING_LEFT_COL)])
         .agg([col(COUNT_LEFT_COL).sum().alias(TOTAL_LEFT_GROUP_COL)]);
 
     let grouped_lf = grouped_lf.clone().join(
         in_group_lf,
-        [col(SELECTION_COLUMN)],
-        [col(SELECTION_COLUMN)],
+        [col(SELECTION_COLUMN), col(MISSING_LEFT_COL)],
+        [col(SELECTION_COLUMN), col(MISSING_LEFT_COL)],
         JoinArgs::new(JoinType::Left),
     );
 
     let out_group_lf = grouped_lf
         .clone()
-        .group_by([col(SELECTION_COLUMN)])
+        .group_by([col(SELECTION_COLUMN), col(MISSING_LEFT_COL)])
         .agg([col(COUNT_RIGHT_COL).sum().alias(TOTAL_RIGHT_GROUP_COL)]);
 
-    let grouped_lf = grouped_lf.clone().join(
+    grouped_lf.clone().join(
         out_group_lf,
-        [col(SELECTION_COLUMN)],
-        [col(SELECTION_COLUMN)],
+        [col(SELECTION_COLUMN), col(MISSING_LEFT_COL)],
+        [col(SELECTION_COLUMN), col(MISSING_LEFT_COL)],
         JoinArgs::new(JoinType::Left),
-    );
-    grouped_lf
+    )
+}
+
+pub(crate) fn add_missing_direction(
+    grouped_lf: &LazyFrame,
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+) -> Result<LazyFrame, TreeError> {
+    // Count the rows with a missing feature value per target:
+    let missing_lf = lf
+        .clone()
+        .filter(col(feature_column).is_null())
+        .group_by([col(target_column).cast(DataType::String)])
+        .agg([col(target_column)
+            .count()
+            .alias(MISSING_COUNT_COL)
+            .cast(DataType::Float64)]);
+    let grouped_lf = grouped_lf
+        .clone()
+        .with_column(col(target_column).cast(DataType::String))
+        .join(
+            missing_lf,
+            [col(target_column)],
+            [col(target_column)],
+            JoinArgs::new(JoinType::Left),
+        )
+        .with_column(col(MISSING_COUNT_COL).fill_null(lit(0.0)));
+
+    // Every split is tried with the missing values in the left group and in the right group:
+    let missing_left_lf = grouped_lf.clone().with_columns([
+        (col(COUNT_LEFT_COL) + col(MISSING_COUNT_COL)).alias(COUNT_LEFT_COL),
+        lit(true).alias(MISSING_LEFT_COL),
+    ]);
+    let missing_right_lf = grouped_lf.with_columns([
+        (col(COUNT_RIGHT_COL) + col(MISSING_COUNT_COL)).alias(COUNT_RIGHT_COL),
+        lit(false).alias(MISSING_LEFT_COL),
+    ]);
+    Ok(
+        concat([missing_left_lf, missing_right_lf], UnionArgs::default())?
+            .drop([MISSING_COUNT_COL]),
+    )
 }
 
 pub(crate) fn pre_process_for_gini(
     lf: &LazyFrame,
     sort_type: SortType,
     feature_column: &str,
+    target_column: &str,
 ) -> LazyFrame {
     // After this this step every feature has the same columns:
     let mut lf = lf
         .clone()
-        .select([col(feature_column), col(TARGET_COLUMN)])
+        .select([col(feature_column), col(target_column)])
         .with_columns([
             lit(feature_column).alias(FEATURE_COLUMN_NAME),
             lit(sort_type.as_str()).alias(SORT_TYPE_COL),
@@ -186,19 +259,47 @@ fn get_unique_combinations(column_1: &str, column_2: &str, lf: &LazyFrame) -> La
         .select([col(column_2)])
         .unique(None, UniqueKeepStrategy::Any);
     // Cross join the two unique sets
-    let combinations = lf1.cross_join(lf2, None);
-    combinations
+    lf1.cross_join(lf2, None)
 }
 
-pub fn get_gini_impurity_for_all_columns(lf: LazyFrame) -> Result<LazyFrame, Box<dyn Error>> {
+// Only a random subset of the features is tried if the settings ask for it, as in a forest:
+pub(crate) fn get_features_to_try<'a>(
+    schema: &'a Schema,
+    target_column: &str,
+    settings: Settings,
+    rng: &mut StdRng,
+) -> Vec<(&'a PlSmallStr, &'a DataType)> {
+    let features: Vec<(&PlSmallStr, &DataType)> = schema
+        .iter()
+        .filter(|(name, _)| name.as_str() != target_column)
+        .collect();
+    match settings.get_max_features() {
+        Some(max_features) if max_features.max(1) < features.len() => {
+            let mut chosen = sample(rng, features.len(), max_features.max(1)).into_vec();
+            chosen.sort_unstable();
+            chosen.into_iter().map(|index| features[index]).collect()
+        }
+        _ => features,
+    }
+}
+
+pub fn get_gini_impurity_for_all_columns(
+    lf: LazyFrame,
+    target_column: &str,
+    settings: Settings,
+    rng: &mut StdRng,
+) -> Result<LazyFrame, TreeError> {
     let schema = lf.logical_plan.compute_schema()?;
     let mut lazy_frames: Vec<LazyFrame> = Vec::new();
-    for (name, dtype) in schema.iter() {
-        if name == TARGET_COLUMN {
-            continue;
-        }
-        let sort_type = get_sort_type_for_dtype(dtype);
-        lazy_frames.push(get_optimal_gini_impurity_for_column(&lf, name, sort_type));
+    for (name, dtype) in get_features_to_try(&schema, target_column, settings, rng) {
+        let sort_type = get_sort_type_for_dtype(name, dtype)?;
+        lazy_frames.push(get_optimal_gini_impurity_for_column(
+            &lf,
+            name,
+            target_column,
+            sort_type,
+            settings,
+        )?);
     }
     let grouped_lf = concat(
         &lazy_frames,
@@ -215,32 +316,104 @@ pub fn get_gini_impurity_for_all_columns(lf: LazyFrame) -> Result<LazyFrame, Box
     Ok(grouped_lf)
 }
 
+pub(crate) fn extract_best_feature(
+    normalized_gini_lf: LazyFrame,
+    sort_type: SortType,
+    feature_dtype: &DataType,
+) -> LazyFrame {
+    // An ordinal split is keyed by its threshold, a categorical split by its categories. Each gets
+    // a column of its own, so that the splits of all features can be combined. Integer thresholds
+    // keep their type, as not every 64 bit integer can be represented by a float:
+    let threshold_column = match sort_type {
+        SortType::Ordinal => Some(get_threshold_column(feature_dtype)),
+        SortType::Categorical => None,
+    };
+    let mut columns = vec![col(FEATURE_COLUMN_NAME), col(SORT_TYPE_COL)];
+    columns.extend(
+        [
+            (SPLIT_THRESHOLD_COL, DataType::Float64),
+            (SPLIT_INTEGER_THRESHOLD_COL, DataType::Int64),
+            (SPLIT_UNSIGNED_THRESHOLD_COL, DataType::UInt64),
+        ]
+        .map(|(column, dtype)| {
+            let threshold = if threshold_column == Some(column) {
+                col(SELECTION_COLUMN).cast(dtype)
+            } else {
+                lit(NULL).cast(dtype)
+            };
+            threshold.alias(column)
+        }),
+    );
+    // The categories of a split are kept as a list, so that a category may hold any character:
+    let categories_dtype = DataType::List(Box::new(DataType::String));
+    let selection = match sort_type {
+        SortType::Ordinal => lit(NULL).cast(categories_dtype),
+        SortType::Categorical => col(SELECTION_COLUMN)
+            .cast(DataType::String)
+            .cast(categories_dtype),
+    };
+    columns.extend([
+        selection.alias(SELECTION_COLUMN),
+        col(MISSING_LEFT_COL),
+        col(NORMALIZED_CHILD_GINI),
+        col(TOTAL_LEFT_GROUP_COL),
+        col(TOTAL_RIGHT_GROUP_COL),
+    ]);
+    // Keep only necessary columns and obtain best result, the lowest impurity for any criterion.
+    // On a tie, which happens when there are no missing values, these are sent right:
+    normalized_gini_lf
+        .select(columns)
+        .sort(
+            [NORMALIZED_CHILD_GINI, MISSING_LEFT_COL],
+            SortMultipleOptions::default(),
+        )
+        .limit(1)
+}
+
+/// The column holding the threshold of an ordinal feature of this type.
+fn get_threshold_column(feature_dtype: &DataType) -> &'static str {
+    match feature_dtype {
+        DataType::UInt64 => SPLIT_UNSIGNED_THRESHOLD_COL,
+        dtype if dtype.is_integer() => SPLIT_INTEGER_THRESHOLD_COL,
+        _ => SPLIT_THRESHOLD_COL,
+    }
+}
+
 // pub fn remove_unused_columns(lf: LazyFrame, gini_lf: LazyFrame) -> LazyFrame{
 //
 // }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::constants::TARGET_COLUMN;
-    use crate::test_utils::{assert_single_row_df_equal, get_preprocessed_test_dataframe};
+    use crate::settings::ThresholdSearch;
+    use crate::test_utils::{
+        assert_single_row_df_equal, get_ordinal_selection_column, get_preprocessed_test_dataframe,
+        TITANIC_TARGET_COLUMN,
+    };
     use polars_core::df;
-    use polars_core::utils::arrow::io::iterator::StreamingIterator;
     use polars_core::utils::Container;
     use polars_lazy::prelude::IntoLazy;
+    use rand::SeedableRng;
+    use std::error::Error;
 
     #[test]
     fn test_debug() -> Result<(), Box<dyn Error>> {
         let mut lf = get_preprocessed_test_dataframe();
-        let collected = get_gini_impurity_for_all_columns(lf.clone())?.collect()?;
+        let collected = get_gini_impurity_for_all_columns(
+            lf.clone(),
+            TITANIC_TARGET_COLUMN,
+            Settings::default(),
+            &mut StdRng::seed_from_u64(0),
+        )?
+        .collect()?;
         let keep_columns = collected.column(FEATURE_COLUMN_NAME)?.str()?;
 
         let mut keep_columns_vec = keep_columns
-                .into_iter()
-                .map(|opt_s| col(opt_s.unwrap_or(""))) // Provide default for None
-                .collect::<Vec<_>>();
-        keep_columns_vec.push(col(TARGET_COLUMN));
+            .into_iter()
+            .map(|opt_s| col(opt_s.unwrap_or(""))) // Provide default for None
+            .collect::<Vec<_>>();
+        keep_columns_vec.push(col(TITANIC_TARGET_COLUMN));
         lf = lf.select(keep_columns_vec);
 
         println!("{:?}", lf.collect());
@@ -254,20 +427,31 @@ mod tests {
         }
 
         let mut lf = get_preprocessed_test_dataframe();
-        lf = lf.drop([TARGET_COLUMN]);
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
         let target_column = "Pclass";
-        lf = lf.rename([target_column], [TARGET_COLUMN], true);
 
-        let collected = get_gini_impurity_for_all_columns(lf.clone())?.first().collect()?;
+        let collected = get_gini_impurity_for_all_columns(
+            lf.clone(),
+            target_column,
+            Settings::default(),
+            &mut StdRng::seed_from_u64(0),
+        )?
+        .first()
+        .collect()?;
 
-        let expected_df = df![
+        let mut expected_df = df![
             FEATURE_COLUMN_NAME => &["Fare"],
             SORT_TYPE_COL => &["ordinal"],
-            SELECTION_COLUMN => &["21.6792"],
+            SPLIT_THRESHOLD_COL => &[21.3771],
+            SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            SELECTION_COLUMN => &[None::<&str>],
+            MISSING_LEFT_COL => &[false],
             NORMALIZED_CHILD_GINI => &[0.434657_f64],
             TOTAL_LEFT_GROUP_COL => &[357.0],
             TOTAL_RIGHT_GROUP_COL => &[534.0],
         ]?;
+        expected_df.with_column(get_ordinal_selection_column())?;
 
         assert_eq!(collected.schema(), expected_df.schema());
         assert_single_row_df_equal(&collected, &expected_df)?;
@@ -275,6 +459,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_iterate_over_random_subset_of_columns() -> Result<(), Box<dyn Error>> {
+        let mut lf = get_preprocessed_test_dataframe();
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
+
+        let mut settings = Settings::default();
+        settings.set_max_features(Some(2));
+        let get_features = |seed: u64| -> Result<Vec<String>, Box<dyn Error>> {
+            let collected = get_gini_impurity_for_all_columns(
+                lf.clone(),
+                "Pclass",
+                settings,
+                &mut StdRng::seed_from_u64(seed),
+            )?
+            .collect()?;
+            let mut features: Vec<String> = collected
+                .column(FEATURE_COLUMN_NAME)?
+                .str()?
+                .into_no_null_iter()
+                .map(|feature| feature.to_string())
+                .collect();
+            features.sort();
+            features.dedup();
+            Ok(features)
+        };
+
+        // Two features are tried, the same ones for the same seed:
+        let features = get_features(42)?;
+        assert_eq!(features.len(), 2);
+        assert_eq!(features, get_features(42)?);
+        Ok(())
+    }
+
     #[test]
     fn test_get_unique_combinations() -> Result<(), Box<dyn Error>> {
         unsafe {
@@ -327,40 +544,138 @@ mod tests {
         }
 
         let mut lf = get_preprocessed_test_dataframe();
-        lf = lf.drop([TARGET_COLUMN]);
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
         let feature_column = "Fare";
         let target_column = "Pclass";
-        lf = lf.rename([target_column], [TARGET_COLUMN], true);
 
-        let final_lf = get_optimal_gini_impurity_for_column(&lf, feature_column, SortType::Ordinal);
+        let final_lf = get_optimal_gini_impurity_for_column(
+            &lf,
+            feature_column,
+            target_column,
+            SortType::Ordinal,
+            Settings::default(),
+        )?;
         let collected = final_lf.collect()?;
-        let expected_df = df![
+        let mut expected_df = df![
             FEATURE_COLUMN_NAME => &["Fare"],
             SORT_TYPE_COL => &["ordinal"],
-            SELECTION_COLUMN => &["21.6792"],
+            SPLIT_THRESHOLD_COL => &[21.3771],
+            SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            SELECTION_COLUMN => &[None::<&str>],
+            MISSING_LEFT_COL => &[false],
             NORMALIZED_CHILD_GINI => &[0.434657_f64],
             TOTAL_LEFT_GROUP_COL => &[357.0],
             TOTAL_RIGHT_GROUP_COL => &[534.0],
         ]?;
+        expected_df.with_column(get_ordinal_selection_column())?;
 
         assert_eq!(collected.schema(), expected_df.schema());
         assert_single_row_df_equal(&collected, &expected_df)?;
         Ok(())
     }
-}
 
-pub(crate) fn extract_best_feature(normalized_gini_lf: LazyFrame) -> LazyFrame {
-    // Keep only necessary columns and obtain best result:
-    let best_result_lf = normalized_gini_lf
-        .select([
-            col(FEATURE_COLUMN_NAME),
-            col(SORT_TYPE_COL),
-            col(SELECTION_COLUMN),
-            col(NORMALIZED_CHILD_GINI),
-            col(TOTAL_LEFT_GROUP_COL),
-            col(TOTAL_RIGHT_GROUP_COL),
-        ])
-        .sort([NORMALIZED_CHILD_GINI], SortMultipleOptions::default())
-        .limit(1);
-    best_result_lf
+    #[test]
+    fn test_gini_for_ordinal_column_exhaustive() -> Result<(), Box<dyn Error>> {
+        let mut lf = get_preprocessed_test_dataframe();
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
+        let feature_column = "Fare";
+        let target_column = "Pclass";
+
+        let mut settings = Settings::default();
+        settings.set_threshold_search(ThresholdSearch::Exhaustive);
+        let final_lf = get_optimal_gini_impurity_for_column(
+            &lf,
+            feature_column,
+            target_column,
+            SortType::Ordinal,
+            settings,
+        )?;
+        let collected = final_lf.collect()?;
+
+        // The best cut lies between the fixed quantiles, and beats the default search:
+        let mut expected_df = df![
+            FEATURE_COLUMN_NAME => &["Fare"],
+            SORT_TYPE_COL => &["ordinal"],
+            SPLIT_THRESHOLD_COL => &[25.5271],
+            SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            SELECTION_COLUMN => &[None::<&str>],
+            MISSING_LEFT_COL => &[false],
+            NORMALIZED_CHILD_GINI => &[0.412544_f64],
+            TOTAL_LEFT_GROUP_COL => &[330.0],
+            TOTAL_RIGHT_GROUP_COL => &[561.0],
+        ]?;
+        expected_df.with_column(get_ordinal_selection_column())?;
+
+        assert_eq!(collected.schema(), expected_df.schema());
+        assert_single_row_df_equal(&collected, &expected_df)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_gini_for_large_integer_column() -> Result<(), Box<dyn Error>> {
+        // Above 2^53 these values can't be told apart as floats, the threshold separates them:
+        let lowest = 1_i64 << 60;
+        let lf = df![
+            "Id" => (0..10).map(|i| lowest + i).collect::<Vec<i64>>(),
+            "Target" => (0..10).map(|i| (i >= 5).to_string()).collect::<Vec<String>>(),
+        ]?
+        .lazy();
+
+        let mut exhaustive = Settings::default();
+        exhaustive.set_threshold_search(ThresholdSearch::Exhaustive);
+        for settings in [Settings::default(), exhaustive] {
+            let collected = get_optimal_gini_impurity_for_column(
+                &lf,
+                "Id",
+                "Target",
+                SortType::Ordinal,
+                settings,
+            )?
+            .collect()?;
+            let mut expected_df = df![
+                FEATURE_COLUMN_NAME => &["Id"],
+                SORT_TYPE_COL => &["ordinal"],
+                SPLIT_THRESHOLD_COL => &[None::<f64>],
+                SPLIT_INTEGER_THRESHOLD_COL => &[lowest + 4],
+                SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+                SELECTION_COLUMN => &[None::<&str>],
+                MISSING_LEFT_COL => &[false],
+                NORMALIZED_CHILD_GINI => &[0.0],
+                TOTAL_LEFT_GROUP_COL => &[5.0],
+                TOTAL_RIGHT_GROUP_COL => &[5.0],
+            ]?;
+            expected_df.with_column(get_ordinal_selection_column())?;
+            assert_eq!(collected.schema(), expected_df.schema());
+            assert_single_row_df_equal(&collected, &expected_df)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_gini_for_ordinal_column_with_few_quantiles() -> Result<(), Box<dyn Error>> {
+        let mut lf = get_preprocessed_test_dataframe();
+        lf = lf.drop([TITANIC_TARGET_COLUMN]);
+        let target_column = "Pclass";
+
+        // With two bins, the midpoint below the median is the only threshold:
+        let mut settings = Settings::default();
+        settings.set_threshold_search(ThresholdSearch::Quantiles(2));
+        let collected = get_optimal_gini_impurity_for_column(
+            &lf,
+            "Fare",
+            target_column,
+            SortType::Ordinal,
+            settings,
+        )?
+        .collect()?;
+        let threshold = collected
+            .column(SPLIT_THRESHOLD_COL)?
+            .f64()?
+            .get(0)
+            .unwrap();
+        assert!((threshold - 14.4271).abs() < 0.00001);
+        Ok(())
+    }
 }
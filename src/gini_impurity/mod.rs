@@ -1,5 +1,7 @@
 mod categorical_columns;
 pub(crate) mod constants;
+#[allow(clippy::module_inception)]
 pub mod gini_impurity;
 mod ordinal_columns;
+pub(crate) mod regression_impurity;
 pub mod sort_type;
@@ -1,29 +1,132 @@
-use crate::constants::TARGET_COLUMN;
+use crate::error::TreeError;
 use crate::gini_impurity::constants::{
-    COUNT_LEFT_COL, COUNT_RIGHT_COL, QUANTILES, SELECTION_COLUMN, TEMP_COLUMN_ORDINAL,
+    COUNT_LEFT_COL, COUNT_RIGHT_COL, LEFT_GROUP_FLAG_COL, SELECTION_COLUMN, TEMP_COLUMN_ORDINAL,
+    THRESHOLD_COLUMN, TOTAL_PER_TARGET_COL,
 };
 use crate::gini_impurity::gini_impurity;
 use crate::gini_impurity::gini_impurity::extract_best_feature;
 use crate::gini_impurity::sort_type::SortType;
-use polars::prelude::{col, lit, UnionArgs};
+use crate::settings::{Settings, ThresholdSearch};
+use polars::prelude::{col, lit, Expr, JoinArgs, JoinType, UnionArgs};
 use polars_core::datatypes::DataType;
+use polars_core::prelude::{SortMultipleOptions, UniqueKeepStrategy};
 use polars_lazy::dsl::concat;
 use polars_lazy::frame::LazyFrame;
 
 pub fn get_optimal_gini_impurity_for_ordinal_column(
     lf: &LazyFrame,
     feature_column: &str,
-) -> LazyFrame {
-    let lf = gini_impurity::pre_process_for_gini(lf, SortType::Ordinal, feature_column);
+    target_column: &str,
+    settings: Settings,
+) -> Result<LazyFrame, TreeError> {
+    let feature_dtype = get_feature_dtype(lf, feature_column)?;
+    let lf =
+        gini_impurity::pre_process_for_gini(lf, SortType::Ordinal, feature_column, target_column);
+
+    let mut grouped_lf = match settings.get_threshold_search() {
+        ThresholdSearch::Quantiles(n_quantiles) => group_by_for_quantiles(
+            &lf,
+            feature_column,
+            target_column,
+            &feature_dtype,
+            n_quantiles,
+        )?,
+        ThresholdSearch::Exhaustive => {
+            group_by_for_all_thresholds(&lf, feature_column, target_column, &feature_dtype)
+        }
+    };
+
+    grouped_lf =
+        gini_impurity::add_missing_direction(&grouped_lf, &lf, feature_column, target_column)?;
+    grouped_lf = gini_impurity::add_totals_of_in_out_group(&grouped_lf);
+    let gini_lf =
+        gini_impurity::compute_gini_per_feature(&grouped_lf, settings.get_split_criterion());
+    let normalized_gini_lf = gini_impurity::normalize_gini_per_group(&grouped_lf, &gini_lf);
+    // Keep only necessary columns and obtain best result:
+    Ok(extract_best_feature(
+        normalized_gini_lf,
+        SortType::Ordinal,
+        &feature_dtype,
+    ))
+}
+
+pub(crate) fn get_feature_dtype(
+    lf: &LazyFrame,
+    feature_column: &str,
+) -> Result<DataType, TreeError> {
+    let schema = lf.clone().collect_schema()?;
+    Ok(schema.try_get(feature_column)?.clone())
+}
+
+pub(crate) fn get_quantiles(n_quantiles: u16) -> Vec<f64> {
+    // The inner boundaries of n equally sized bins:
+    (1..n_quantiles)
+        .map(|i| i as f64 / n_quantiles as f64)
+        .collect()
+}
 
+pub(crate) fn get_ordinal_candidate_splits(
+    lf: &LazyFrame,
+    feature_column: &str,
+    feature_dtype: &DataType,
+    settings: Settings,
+) -> Vec<LazyFrame> {
+    match settings.get_threshold_search() {
+        // The rows of every quantile, flagged by whether they fall in the left group:
+        ThresholdSearch::Quantiles(n_quantiles) => get_quantiles(n_quantiles)
+            .iter()
+            .map(|quantile| {
+                add_quantile_selection(lf, feature_column, feature_dtype, *quantile).rename(
+                    [TEMP_COLUMN_ORDINAL],
+                    [LEFT_GROUP_FLAG_COL],
+                    true,
+                )
+            })
+            .collect(),
+        // Every row paired with every threshold. Note that this grows with the number of rows times
+        // the number of distinct values, only use it when the impurity can't be accumulated, like
+        // the absolute error around the median:
+        ThresholdSearch::Exhaustive => {
+            let thresholds_lf = get_thresholds(lf, feature_column, feature_dtype)
+                .select([col(THRESHOLD_COLUMN)])
+                .filter(col(THRESHOLD_COLUMN).is_not_null());
+            let candidates_lf = lf
+                .clone()
+                .cross_join(thresholds_lf, None)
+                .with_columns([
+                    col(THRESHOLD_COLUMN).alias(SELECTION_COLUMN),
+                    col(feature_column)
+                        .gt(col(THRESHOLD_COLUMN))
+                        .alias(LEFT_GROUP_FLAG_COL),
+                ])
+                .drop([feature_column, THRESHOLD_COLUMN]);
+            vec![candidates_lf]
+        }
+    }
+}
+
+fn group_by_for_quantiles(
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+    feature_dtype: &DataType,
+    n_quantiles: u16,
+) -> Result<LazyFrame, TreeError> {
     // Gather lazy frames for every quantile:
     let mut lazy_frames: Vec<LazyFrame> = Vec::new();
-    for quantile in QUANTILES.iter() {
-        lazy_frames.push(group_by_for_single_quantile(&lf, feature_column, *quantile))
+    for quantile in get_quantiles(n_quantiles).iter() {
+        lazy_frames.push(group_by_for_single_quantile(
+            lf,
+            feature_column,
+            target_column,
+            feature_dtype,
+            *quantile,
+        ))
     }
 
-    // Combine lazyframes into single, larger lazyframe:
-    let mut grouped_lf = concat(
+    // Combine lazyframes into single, larger lazyframe. Neighbouring quantiles can share a value,
+    // keep each of them only once:
+    Ok(concat(
         &lazy_frames,
         UnionArgs {
             rechunk: true,
@@ -33,49 +136,158 @@ pub fn get_optimal_gini_impurity_for_ordinal_column(
             parallel: true,
             maintain_order: false,
         },
-    )
-    .unwrap();
+    )?
+    .unique(None, UniqueKeepStrategy::Any))
+}
 
-    grouped_lf = gini_impurity::add_totals_of_in_out_group(&grouped_lf);
-    let gini_lf = gini_impurity::compute_gini_per_feature(&grouped_lf);
-    let normalized_gini_lf = gini_impurity::normalize_gini_per_group(&grouped_lf, &gini_lf);
-    // Keep only necessary columns and obtain best result:
-    let final_lf = extract_best_feature(normalized_gini_lf);
-    final_lf
+pub(crate) fn get_thresholds(
+    lf: &LazyFrame,
+    feature_column: &str,
+    feature_dtype: &DataType,
+) -> LazyFrame {
+    // Pair every distinct value with the midpoint to the next smaller value. Splitting on "greater
+    // than the midpoint" puts the distinct value and everything above it in the left group:
+    let value = col(feature_column);
+    lf.clone()
+        .select([col(feature_column)])
+        .filter(col(feature_column).is_not_null())
+        .unique(None, UniqueKeepStrategy::Any)
+        .sort(
+            [feature_column],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .with_column(
+            get_midpoint(value.clone(), value.shift(lit(-1)), feature_dtype)
+                .alias(THRESHOLD_COLUMN),
+        )
+}
+
+fn get_midpoint(upper: Expr, lower: Expr, feature_dtype: &DataType) -> Expr {
+    if !feature_dtype.is_integer() {
+        return (upper.cast(DataType::Float64) + lower.cast(DataType::Float64)) / lit(2.0);
+    }
+    // Integers are halved in their own type, which can't overflow and rounds down. The midpoint
+    // is then at least the lower value and below the upper value, so it separates them exactly:
+    let two = lit(2).cast(feature_dtype.clone());
+    let one = lit(1).cast(feature_dtype.clone());
+    upper.clone().floor_div(two.clone()) + lower.clone().floor_div(two) + upper.and(lower).and(one)
 }
 
-fn group_by_for_single_quantile(lf: &LazyFrame, feature_column: &str, quantile: f64) -> LazyFrame {
-    // Add quantile as string:
-    let mut quantile_lf = lf.clone().with_column(
-        col(feature_column)
-            .quantile(lit(quantile), Default::default())
-            .cast(DataType::String)
-            .alias(SELECTION_COLUMN),
+fn group_by_for_all_thresholds(
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+    feature_dtype: &DataType,
+) -> LazyFrame {
+    // Count every combination of distinct value and target:
+    let mut grouped_lf = lf
+        .clone()
+        .filter(col(feature_column).is_not_null())
+        .group_by([col("*")])
+        .agg([col(target_column)
+            .count()
+            .alias(COUNT_LEFT_COL)
+            .cast(DataType::Float64)]);
+    grouped_lf =
+        gini_impurity::add_zero_count(feature_column, target_column, COUNT_LEFT_COL, &grouped_lf);
+
+    // Sorted from high to low, the cumulative count is the count of the left group:
+    grouped_lf = grouped_lf
+        .join(
+            get_thresholds(lf, feature_column, feature_dtype),
+            [col(feature_column)],
+            [col(feature_column)],
+            JoinArgs::new(JoinType::Left),
+        )
+        .sort(
+            [feature_column],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .with_columns([
+            col(COUNT_LEFT_COL)
+                .cum_sum(false)
+                .over([col(target_column)])
+                .alias(COUNT_LEFT_COL),
+            col(COUNT_LEFT_COL)
+                .sum()
+                .over([col(target_column)])
+                .alias(TOTAL_PER_TARGET_COL),
+        ]);
+
+    // Add count out, the smallest value has no threshold as its right group is empty:
+    grouped_lf
+        .with_columns([
+            (col(TOTAL_PER_TARGET_COL) - col(COUNT_LEFT_COL)).alias(COUNT_RIGHT_COL),
+            col(THRESHOLD_COLUMN).alias(SELECTION_COLUMN),
+        ])
+        .filter(col(THRESHOLD_COLUMN).is_not_null())
+        .drop([TOTAL_PER_TARGET_COL, THRESHOLD_COLUMN, feature_column])
+        .sort([SELECTION_COLUMN, target_column], Default::default())
+}
+
+fn group_by_for_single_quantile(
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+    feature_dtype: &DataType,
+    quantile: f64,
+) -> LazyFrame {
+    // Missing values are added to either group later on:
+    let quantile_lf = add_quantile_selection(
+        &lf.clone().filter(col(feature_column).is_not_null()),
+        feature_column,
+        feature_dtype,
+        quantile,
     );
-    quantile_lf = quantile_lf
+    group_by_for_ordinal_inner(&quantile_lf, target_column)
+}
+
+fn add_quantile_selection(
+    lf: &LazyFrame,
+    feature_column: &str,
+    feature_dtype: &DataType,
+    quantile: f64,
+) -> LazyFrame {
+    // The threshold is the midpoint between the quantile and the largest value below it, so the
+    // left group holds the quantile and everything above it. There is none for the smallest value:
+    let value = col(feature_column);
+    let quantile_value = get_nearest_quantile(value.clone(), quantile);
+    let value_below = value
+        .clone()
+        .filter(value.clone().lt(quantile_value.clone()))
+        .max();
+    lf.clone()
         .with_column(
-            col(feature_column)
-                .gt_eq(col(feature_column).quantile(lit(quantile), Default::default()))
-                .alias(TEMP_COLUMN_ORDINAL),
+            get_midpoint(quantile_value, value_below, feature_dtype).alias(SELECTION_COLUMN),
         )
-        .drop([feature_column]);
-    let grouped_lf = group_by_for_ordinal_inner(&quantile_lf);
-    grouped_lf
+        .filter(col(SELECTION_COLUMN).is_not_null())
+        .with_column(value.gt(col(SELECTION_COLUMN)).alias(TEMP_COLUMN_ORDINAL))
+        .drop([feature_column])
+}
+
+fn get_nearest_quantile(value: Expr, quantile: f64) -> Expr {
+    // The observed value nearest to the quantile, picked from the sorted values rather than
+    // interpolated, so that it keeps the type of the feature. Missing if there are no values:
+    let sorted = value.drop_nulls().sort(Default::default());
+    let position = ((sorted.clone().len().cast(DataType::Float64) - lit(1.0)) * lit(quantile))
+        .round(0)
+        .cast(DataType::Int64);
+    sorted.slice(position, lit(1)).first()
 }
 
-fn group_by_for_ordinal_inner(lf: &LazyFrame) -> LazyFrame {
+fn group_by_for_ordinal_inner(lf: &LazyFrame, target_column: &str) -> LazyFrame {
     let mut grouped_lf = lf
         // Add count in:
         .clone()
         .group_by([col("*")])
-        .agg([col(TARGET_COLUMN)
+        .agg([col(target_column)
             .count()
             .alias(COUNT_LEFT_COL)
             .cast(DataType::Float64)]);
 
     grouped_lf = gini_impurity::add_zero_count(
         TEMP_COLUMN_ORDINAL,
-        TARGET_COLUMN,
+        target_column,
         COUNT_LEFT_COL,
         &grouped_lf,
     );
@@ -84,11 +296,11 @@ fn group_by_for_ordinal_inner(lf: &LazyFrame) -> LazyFrame {
     grouped_lf = grouped_lf
         .with_columns([col(COUNT_LEFT_COL)
             .sum()
-            .over([col(TARGET_COLUMN)])
-            .alias("total_per_target")])
-        .with_columns([(col("total_per_target") - col(COUNT_LEFT_COL)).alias(COUNT_RIGHT_COL)])
+            .over([col(target_column)])
+            .alias(TOTAL_PER_TARGET_COL)])
+        .with_columns([(col(TOTAL_PER_TARGET_COL) - col(COUNT_LEFT_COL)).alias(COUNT_RIGHT_COL)])
         .filter(col(TEMP_COLUMN_ORDINAL))
-        .drop(["total_per_target", TEMP_COLUMN_ORDINAL])
-        .sort([SELECTION_COLUMN, TARGET_COLUMN], Default::default());
+        .drop([TOTAL_PER_TARGET_COL, TEMP_COLUMN_ORDINAL])
+        .sort([SELECTION_COLUMN, target_column], Default::default());
     grouped_lf
 }
@@ -0,0 +1,615 @@
+use crate::error::TreeError;
+use crate::gini_impurity::categorical_columns::get_categorical_candidate_splits;
+use crate::gini_impurity::constants::{
+    CANDIDATE_COLUMN, CATEGORY_RANK_COL, COUNT_LEFT_COL, COUNT_RIGHT_COL, FEATURE_COLUMN_NAME,
+    GROUP_COUNT_COL, GROUP_IMPURITY_COL, IMPURITY_LEFT_GROUP_COL, IMPURITY_RIGHT_GROUP_COL,
+    LEFT_GROUP_FLAG_COL, MISSING_COUNT_COL, MISSING_LEFT_COL, MISSING_SQUARES_COL, MISSING_SUM_COL,
+    NORMALIZED_CHILD_GINI, SELECTION_COLUMN, SORT_TYPE_COL, SQUARES_LEFT_COL, SQUARES_RIGHT_COL,
+    SUM_LEFT_COL, SUM_RIGHT_COL, THRESHOLD_COLUMN, TOTAL_LEFT_GROUP_COL, TOTAL_RIGHT_GROUP_COL,
+};
+use crate::gini_impurity::gini_impurity::{
+    extract_best_feature, get_features_to_try, pre_process_for_gini,
+};
+use crate::gini_impurity::ordinal_columns::{
+    get_feature_dtype, get_ordinal_candidate_splits, get_thresholds,
+};
+use crate::gini_impurity::sort_type::{get_sort_type_for_dtype, SortType};
+use crate::old_preprocessing::REDUNDANT_STRING_VALUE;
+use crate::settings::{CategoricalSearch, RegressionCriterion, Settings, ThresholdSearch};
+use polars::prelude::{col, lit, not, when, Expr, JoinArgs, JoinType, UnionArgs, NULL};
+use polars_core::datatypes::DataType;
+use polars_core::df;
+use polars_core::prelude::{NamedFrom, Series, SortMultipleOptions, UniqueKeepStrategy};
+use polars_lazy::frame::{IntoLazy, LazyFrame};
+use polars_lazy::prelude::concat;
+use rand::rngs::StdRng;
+
+pub(crate) fn get_impurity_expression(target_column: &str, criterion: RegressionCriterion) -> Expr {
+    let target = col(target_column);
+    match criterion {
+        // Mean squared deviation from the mean:
+        RegressionCriterion::SquaredError => target.var(0),
+        // Mean absolute deviation from the median:
+        RegressionCriterion::AbsoluteError => (target.clone() - target.median()).abs().mean(),
+    }
+}
+
+pub(crate) fn get_leaf_value_expression(
+    target_column: &str,
+    criterion: RegressionCriterion,
+) -> Expr {
+    match criterion {
+        RegressionCriterion::SquaredError => col(target_column).mean(),
+        RegressionCriterion::AbsoluteError => col(target_column).median(),
+    }
+}
+
+pub(crate) fn add_missing_direction_to_candidates(
+    candidates_lf: &LazyFrame,
+) -> Result<LazyFrame, TreeError> {
+    // Rows with a missing feature value have no flag, try them in the left and in the right group:
+    let missing_left_lf = candidates_lf.clone().with_columns([
+        col(LEFT_GROUP_FLAG_COL).fill_null(lit(true)),
+        lit(true).alias(MISSING_LEFT_COL),
+    ]);
+    let missing_right_lf = candidates_lf.clone().with_columns([
+        col(LEFT_GROUP_FLAG_COL).fill_null(lit(false)),
+        lit(false).alias(MISSING_LEFT_COL),
+    ]);
+    Ok(concat(
+        [missing_left_lf, missing_right_lf],
+        UnionArgs::default(),
+    )?)
+}
+
+pub(crate) fn compute_impurity_per_group(
+    candidates_lf: &LazyFrame,
+    target_column: &str,
+    criterion: RegressionCriterion,
+) -> LazyFrame {
+    // Count and score the left and right group of every candidate split:
+    candidates_lf
+        .clone()
+        .group_by([
+            col(FEATURE_COLUMN_NAME),
+            col(SORT_TYPE_COL),
+            col(SELECTION_COLUMN),
+            col(MISSING_LEFT_COL),
+            col(LEFT_GROUP_FLAG_COL),
+        ])
+        .agg([
+            col(target_column)
+                .count()
+                .cast(DataType::Float64)
+                .alias(GROUP_COUNT_COL),
+            get_impurity_expression(target_column, criterion)
+                .cast(DataType::Float64)
+                .alias(GROUP_IMPURITY_COL),
+        ])
+}
+
+pub(crate) fn normalize_impurity_per_group(grouped_lf: &LazyFrame) -> LazyFrame {
+    let keys = [
+        col(FEATURE_COLUMN_NAME),
+        col(SORT_TYPE_COL),
+        col(SELECTION_COLUMN),
+        col(MISSING_LEFT_COL),
+    ];
+    let left_lf = grouped_lf.clone().filter(col(LEFT_GROUP_FLAG_COL)).select([
+        col(FEATURE_COLUMN_NAME),
+        col(SORT_TYPE_COL),
+        col(SELECTION_COLUMN),
+        col(MISSING_LEFT_COL),
+        col(GROUP_COUNT_COL).alias(TOTAL_LEFT_GROUP_COL),
+        col(GROUP_IMPURITY_COL).alias(IMPURITY_LEFT_GROUP_COL),
+    ]);
+    let right_lf = grouped_lf
+        .clone()
+        .filter(not(col(LEFT_GROUP_FLAG_COL)))
+        .select([
+            col(FEATURE_COLUMN_NAME),
+            col(SORT_TYPE_COL),
+            col(SELECTION_COLUMN),
+            col(MISSING_LEFT_COL),
+            col(GROUP_COUNT_COL).alias(TOTAL_RIGHT_GROUP_COL),
+            col(GROUP_IMPURITY_COL).alias(IMPURITY_RIGHT_GROUP_COL),
+        ]);
+
+    // Splits that leave one of the groups empty are dropped by the inner join:
+    left_lf
+        .join(right_lf, keys.clone(), keys, JoinArgs::new(JoinType::Inner))
+        .with_column(
+            ((col(IMPURITY_LEFT_GROUP_COL) * col(TOTAL_LEFT_GROUP_COL)
+                + col(IMPURITY_RIGHT_GROUP_COL) * col(TOTAL_RIGHT_GROUP_COL))
+                / (col(TOTAL_LEFT_GROUP_COL) + col(TOTAL_RIGHT_GROUP_COL)))
+            .alias(NORMALIZED_CHILD_GINI),
+        )
+}
+
+fn get_squared_error_for_all_thresholds(
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+    feature_dtype: &DataType,
+) -> Result<LazyFrame, TreeError> {
+    // Centered on the mean, which leaves the squared error as it is but keeps the sums of squares
+    // small enough to subtract from each other:
+    let target = col(target_column).cast(DataType::Float64);
+    let lf = lf
+        .clone()
+        .with_column((target.clone() - target.mean()).alias(target_column));
+    let missing_lf = lf.clone().filter(col(feature_column).is_null()).select([
+        col(target_column)
+            .count()
+            .cast(DataType::Float64)
+            .alias(MISSING_COUNT_COL),
+        col(target_column).sum().alias(MISSING_SUM_COL),
+        col(target_column).pow(2).sum().alias(MISSING_SQUARES_COL),
+    ]);
+
+    // Sorted from high to low, the cumulative sums are the ones of the left group and the totals
+    // minus these the ones of the right group. The smallest value has no threshold:
+    let sums = [
+        (COUNT_LEFT_COL, COUNT_RIGHT_COL),
+        (SUM_LEFT_COL, SUM_RIGHT_COL),
+        (SQUARES_LEFT_COL, SQUARES_RIGHT_COL),
+    ];
+    let sums_lf = lf
+        .clone()
+        .filter(col(feature_column).is_not_null())
+        .group_by([col(feature_column)])
+        .agg([
+            col(target_column)
+                .count()
+                .cast(DataType::Float64)
+                .alias(COUNT_LEFT_COL),
+            col(target_column).sum().alias(SUM_LEFT_COL),
+            col(target_column).pow(2).sum().alias(SQUARES_LEFT_COL),
+        ])
+        .join(
+            get_thresholds(&lf, feature_column, feature_dtype),
+            [col(feature_column)],
+            [col(feature_column)],
+            JoinArgs::new(JoinType::Left),
+        )
+        .sort(
+            [feature_column],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .with_columns(
+            sums.iter()
+                .flat_map(|(left, right)| {
+                    [
+                        col(*left).cum_sum(false).alias(*left),
+                        (col(*left).sum() - col(*left).cum_sum(false)).alias(*right),
+                    ]
+                })
+                .collect::<Vec<Expr>>(),
+        )
+        .filter(col(THRESHOLD_COLUMN).is_not_null())
+        .cross_join(missing_lf, None);
+
+    // Rows with a missing feature value are added to the left or to the right group:
+    let lazy_frames = [true, false].map(|missing_left| {
+        let add_missing = |column: &str, missing_column: &str, is_missing_group: bool| {
+            if is_missing_group {
+                col(column) + col(missing_column)
+            } else {
+                col(column)
+            }
+        };
+        let count_left = add_missing(COUNT_LEFT_COL, MISSING_COUNT_COL, missing_left);
+        let sum_left = add_missing(SUM_LEFT_COL, MISSING_SUM_COL, missing_left);
+        let squares_left = add_missing(SQUARES_LEFT_COL, MISSING_SQUARES_COL, missing_left);
+        let count_right = add_missing(COUNT_RIGHT_COL, MISSING_COUNT_COL, !missing_left);
+        let sum_right = add_missing(SUM_RIGHT_COL, MISSING_SUM_COL, !missing_left);
+        let squares_right = add_missing(SQUARES_RIGHT_COL, MISSING_SQUARES_COL, !missing_left);
+        // The squared error of a group is the sum of squares minus the square of the sum over n:
+        let squared_error_left = squares_left - sum_left.clone() * sum_left / count_left.clone();
+        let squared_error_right =
+            squares_right - sum_right.clone() * sum_right / count_right.clone();
+        sums_lf.clone().select([
+            lit(feature_column).alias(FEATURE_COLUMN_NAME),
+            lit(SortType::Ordinal.as_str()).alias(SORT_TYPE_COL),
+            col(THRESHOLD_COLUMN).alias(SELECTION_COLUMN),
+            lit(missing_left).alias(MISSING_LEFT_COL),
+            ((squared_error_left + squared_error_right)
+                / (count_left.clone() + count_right.clone()))
+            .alias(NORMALIZED_CHILD_GINI),
+            count_left.alias(TOTAL_LEFT_GROUP_COL),
+            count_right.alias(TOTAL_RIGHT_GROUP_COL),
+        ])
+    });
+    Ok(concat(lazy_frames, UnionArgs::default())?)
+}
+
+fn get_best_category_subset(
+    lf: &LazyFrame,
+    target_column: &str,
+    criterion: RegressionCriterion,
+) -> Result<LazyFrame, TreeError> {
+    // Sorted by the value they predict, the mean for the squared error and the median for the
+    // absolute error, the best subset is a prefix of the categories. The filler string is left
+    // out, as it never joins the left group:
+    let value_column = get_leaf_value_expression(target_column, criterion)
+        .cast(DataType::Float64)
+        .alias(target_column);
+    let ordered_df = lf
+        .clone()
+        .select([
+            col(SELECTION_COLUMN).cast(DataType::String),
+            col(target_column),
+        ])
+        .filter(
+            col(SELECTION_COLUMN)
+                .is_not_null()
+                .and(col(SELECTION_COLUMN).neq(lit(REDUNDANT_STRING_VALUE))),
+        )
+        .group_by([col(SELECTION_COLUMN)])
+        .agg([value_column])
+        .sort(
+            [target_column, SELECTION_COLUMN],
+            SortMultipleOptions::default(),
+        )
+        .collect()?;
+    let categories: Vec<&str> = ordered_df
+        .column(SELECTION_COLUMN)?
+        .str()?
+        .into_no_null_iter()
+        .collect();
+    let n_categories = categories.len() as u32;
+
+    // A prefix is selected by its size, rows of the filler string always go right:
+    let ranks_df = df![
+        SELECTION_COLUMN => categories.clone(),
+        CATEGORY_RANK_COL => (0..n_categories).collect::<Vec<u32>>(),
+    ]?;
+    let sizes_df = df![CANDIDATE_COLUMN => (1..=n_categories).collect::<Vec<u32>>()]?;
+    let candidates_lf = lf
+        .clone()
+        .with_column(col(SELECTION_COLUMN).cast(DataType::String))
+        .join(
+            ranks_df.lazy(),
+            [col(SELECTION_COLUMN)],
+            [col(SELECTION_COLUMN)],
+            JoinArgs::new(JoinType::Left),
+        )
+        .cross_join(sizes_df.lazy(), None)
+        .with_column(
+            when(col(SELECTION_COLUMN).is_null())
+                .then(lit(NULL).cast(DataType::Boolean))
+                .otherwise(
+                    col(CATEGORY_RANK_COL)
+                        .lt(col(CANDIDATE_COLUMN))
+                        .fill_null(lit(false)),
+                )
+                .alias(LEFT_GROUP_FLAG_COL),
+        )
+        .drop([SELECTION_COLUMN, CATEGORY_RANK_COL])
+        .rename([CANDIDATE_COLUMN], [SELECTION_COLUMN], true);
+
+    let candidates_lf = add_missing_direction_to_candidates(&candidates_lf)?;
+    let normalized_lf = normalize_impurity_per_group(&compute_impurity_per_group(
+        &candidates_lf,
+        target_column,
+        criterion,
+    ));
+    let mut best_df =
+        extract_best_feature(normalized_lf, SortType::Categorical, &DataType::String).collect()?;
+    if best_df.height() == 0 {
+        return Ok(best_df.lazy());
+    }
+
+    // The size of the best prefix is replaced by its categories:
+    let selection = best_df
+        .column(SELECTION_COLUMN)?
+        .list()?
+        .get_as_series(0)
+        .ok_or(TreeError::EmptyNode)?;
+    let size = selection.str()?.get(0).ok_or(TreeError::EmptyNode)?;
+    let size: usize = size
+        .parse()
+        .map_err(|_| TreeError::InvalidSplit(size.to_string()))?;
+    let mut subset = categories[..size].to_vec();
+    subset.sort();
+    best_df.with_column(Series::new(
+        SELECTION_COLUMN.into(),
+        [Series::new("".into(), subset)],
+    ))?;
+    Ok(best_df.lazy())
+}
+
+pub(crate) fn get_optimal_regression_impurity_for_column(
+    lf: &LazyFrame,
+    feature_column: &str,
+    target_column: &str,
+    sort_type: SortType,
+    settings: Settings,
+    criterion: RegressionCriterion,
+) -> Result<LazyFrame, TreeError> {
+    let feature_dtype = get_feature_dtype(lf, feature_column)?;
+    let lf = pre_process_for_gini(lf, sort_type, feature_column, target_column);
+    let is_exhaustive = settings.get_threshold_search() == ThresholdSearch::Exhaustive;
+    let normalized_lf = match sort_type {
+        // The squared error of every threshold follows from cumulative sums:
+        SortType::Ordinal if is_exhaustive && criterion == RegressionCriterion::SquaredError => {
+            get_squared_error_for_all_thresholds(
+                &lf,
+                feature_column,
+                target_column,
+                &feature_dtype,
+            )?
+        }
+        SortType::Ordinal => {
+            // Neighbouring quantiles can share a value, keep each candidate only once:
+            let lazy_frames =
+                get_ordinal_candidate_splits(&lf, feature_column, &feature_dtype, settings)
+                    .iter()
+                    .map(|candidates_lf| {
+                        let candidates_lf = add_missing_direction_to_candidates(candidates_lf)?;
+                        Ok(compute_impurity_per_group(
+                            &candidates_lf,
+                            target_column,
+                            criterion,
+                        ))
+                    })
+                    .collect::<Result<Vec<LazyFrame>, TreeError>>()?;
+            normalize_impurity_per_group(
+                &concat(&lazy_frames, UnionArgs::default())?.unique(None, UniqueKeepStrategy::Any),
+            )
+        }
+        SortType::Categorical
+            if settings.get_categorical_search() == CategoricalSearch::Subsets =>
+        {
+            return get_best_category_subset(&lf, target_column, criterion);
+        }
+        SortType::Categorical => {
+            let candidates_lf = get_categorical_candidate_splits(&lf);
+            let candidates_lf = add_missing_direction_to_candidates(&candidates_lf)?;
+            normalize_impurity_per_group(&compute_impurity_per_group(
+                &candidates_lf,
+                target_column,
+                criterion,
+            ))
+        }
+    };
+    Ok(extract_best_feature(
+        normalized_lf,
+        sort_type,
+        &feature_dtype,
+    ))
+}
+
+pub fn get_regression_impurity_for_all_columns(
+    lf: LazyFrame,
+    target_column: &str,
+    settings: Settings,
+    criterion: RegressionCriterion,
+    rng: &mut StdRng,
+) -> Result<LazyFrame, TreeError> {
+    let schema = lf.logical_plan.compute_schema()?;
+    let mut lazy_frames: Vec<LazyFrame> = Vec::new();
+    for (name, dtype) in get_features_to_try(&schema, target_column, settings, rng) {
+        let sort_type = get_sort_type_for_dtype(name, dtype)?;
+        lazy_frames.push(get_optimal_regression_impurity_for_column(
+            &lf,
+            name,
+            target_column,
+            sort_type,
+            settings,
+            criterion,
+        )?);
+    }
+    let grouped_lf = concat(
+        &lazy_frames,
+        UnionArgs {
+            rechunk: true,
+            to_supertypes: false,
+            diagonal: false,
+            from_partitioned_ds: false,
+            parallel: true,
+            maintain_order: false,
+        },
+    )?
+    .sort([NORMALIZED_CHILD_GINI], SortMultipleOptions::default());
+    Ok(grouped_lf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ThresholdSearch;
+    use crate::test_utils::{
+        assert_single_row_df_equal, get_preprocessed_test_dataframe, TITANIC_TARGET_COLUMN,
+    };
+    use rand::SeedableRng;
+    use std::error::Error;
+
+    fn get_fare_target_dataframe() -> LazyFrame {
+        get_preprocessed_test_dataframe().drop([TITANIC_TARGET_COLUMN])
+    }
+
+    #[test]
+    fn test_squared_error_for_ordinal_column() -> Result<(), Box<dyn Error>> {
+        let lf = get_fare_target_dataframe();
+        let collected = get_optimal_regression_impurity_for_column(
+            &lf,
+            "Pclass",
+            "Fare",
+            SortType::Ordinal,
+            Settings::default(),
+            RegressionCriterion::SquaredError,
+        )?
+        .collect()?;
+
+        assert_eq!(collected.height(), 1);
+        let total_left = collected
+            .column(TOTAL_LEFT_GROUP_COL)?
+            .f64()?
+            .get(0)
+            .unwrap();
+        let total_right = collected
+            .column(TOTAL_RIGHT_GROUP_COL)?
+            .f64()?
+            .get(0)
+            .unwrap();
+        assert_eq!(total_left + total_right, 891.0);
+
+        // A split must improve on the variance of the full target:
+        let full_variance = lf
+            .select([col("Fare").var(0)])
+            .collect()?
+            .column("Fare")?
+            .f64()?
+            .get(0)
+            .unwrap();
+        let child_variance = collected
+            .column(NORMALIZED_CHILD_GINI)?
+            .f64()?
+            .get(0)
+            .unwrap();
+        assert!(child_variance < full_variance);
+        Ok(())
+    }
+
+    #[test]
+    fn test_absolute_error_for_categorical_column() -> Result<(), Box<dyn Error>> {
+        let lf = get_fare_target_dataframe();
+        let collected = get_optimal_regression_impurity_for_column(
+            &lf,
+            "Sex",
+            "Fare",
+            SortType::Categorical,
+            Settings::default(),
+            RegressionCriterion::AbsoluteError,
+        )?
+        .collect()?;
+
+        assert_eq!(collected.height(), 1);
+        let total_left = collected
+            .column(TOTAL_LEFT_GROUP_COL)?
+            .f64()?
+            .get(0)
+            .unwrap();
+        let total_right = collected
+            .column(TOTAL_RIGHT_GROUP_COL)?
+            .f64()?
+            .get(0)
+            .unwrap();
+        assert_eq!(total_left + total_right, 891.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_regression_impurity_for_all_columns() -> Result<(), Box<dyn Error>> {
+        let lf = get_fare_target_dataframe();
+        let collected = get_regression_impurity_for_all_columns(
+            lf,
+            "Fare",
+            Settings::default(),
+            RegressionCriterion::SquaredError,
+            &mut StdRng::seed_from_u64(0),
+        )?
+        .first()
+        .collect()?;
+        assert_eq!(
+            collected.column(FEATURE_COLUMN_NAME)?.str()?.get(0),
+            Some("Pclass")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_squared_error_for_ordinal_column_exhaustive() -> Result<(), Box<dyn Error>> {
+        let lf = get_fare_target_dataframe();
+        let get_child_variance = |settings: Settings| -> Result<f64, Box<dyn Error>> {
+            let collected = get_optimal_regression_impurity_for_column(
+                &lf,
+                "Age",
+                "Fare",
+                SortType::Ordinal,
+                settings,
+                RegressionCriterion::SquaredError,
+            )?
+            .collect()?;
+            Ok(collected
+                .column(NORMALIZED_CHILD_GINI)?
+                .f64()?
+                .get(0)
+                .unwrap())
+        };
+
+        // Every quantile threshold is also tried by the exhaustive search:
+        let mut settings = Settings::default();
+        let quantile_variance = get_child_variance(settings)?;
+        settings.set_threshold_search(ThresholdSearch::Exhaustive);
+        let exhaustive_variance = get_child_variance(settings)?;
+        assert!(exhaustive_variance <= quantile_variance);
+        Ok(())
+    }
+
+    #[test]
+    fn test_squared_error_skips_missing_targets() -> Result<(), Box<dyn Error>> {
+        // Rows without a target are left out of the counts, just like out of the sums:
+        let lf = get_fare_target_dataframe().with_column(
+            when(col("Age").lt(lit(30)))
+                .then(lit(NULL))
+                .otherwise(col("Fare"))
+                .alias("Fare"),
+        );
+        let get_best_split = |threshold_search: ThresholdSearch| -> Result<_, Box<dyn Error>> {
+            let mut settings = Settings::default();
+            settings.set_threshold_search(threshold_search);
+            Ok(get_optimal_regression_impurity_for_column(
+                &lf,
+                "Pclass",
+                "Fare",
+                SortType::Ordinal,
+                settings,
+                RegressionCriterion::SquaredError,
+            )?
+            .collect()?)
+        };
+
+        // "Pclass" has fewer values than quantiles, both searches try the same thresholds:
+        let exhaustive = get_best_split(ThresholdSearch::Exhaustive)?;
+        let quantiles = get_best_split(ThresholdSearch::Quantiles(10))?;
+        assert_single_row_df_equal(&exhaustive, &quantiles)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_squared_error_pass_matches_candidates() -> Result<(), Box<dyn Error>> {
+        // "Age" has missing values, which are tried in either group by both searches:
+        let lf = get_fare_target_dataframe();
+        let mut settings = Settings::default();
+        settings.set_threshold_search(ThresholdSearch::Exhaustive);
+        let collected = get_optimal_regression_impurity_for_column(
+            &lf,
+            "Age",
+            "Fare",
+            SortType::Ordinal,
+            settings,
+            RegressionCriterion::SquaredError,
+        )?
+        .collect()?;
+
+        // The same split, scored on every row paired with every threshold:
+        let feature_dtype = get_feature_dtype(&lf, "Age")?;
+        let lf = pre_process_for_gini(&lf, SortType::Ordinal, "Age", "Fare");
+        let candidates_lf =
+            get_ordinal_candidate_splits(&lf, "Age", &feature_dtype, settings).remove(0);
+        let candidates_lf = add_missing_direction_to_candidates(&candidates_lf)?;
+        let grouped_lf =
+            compute_impurity_per_group(&candidates_lf, "Fare", RegressionCriterion::SquaredError);
+        let expected = extract_best_feature(
+            normalize_impurity_per_group(&grouped_lf),
+            SortType::Ordinal,
+            &feature_dtype,
+        )
+        .collect()?;
+        assert_eq!(collected.schema(), expected.schema());
+        assert_single_row_df_equal(&collected, &expected)?;
+        Ok(())
+    }
+}
@@ -2,27 +2,34 @@
 Sort type governs how a column can be split left/right.
 For ordinal, this is be greater-than/lesser-than a threshold.
 For categorical, this equal/not-equal to a category
+Dates, datetimes, durations and enums are ordinal, booleans and categoricals are categorical.
+These are split on their encoded values, see FeatureEncoding.
 */
 
+use crate::error::TreeError;
 use polars_core::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum SortType {
     Ordinal,
     Categorical,
 }
 
-impl SortType {
-    pub fn from_str(string: &str) -> Self {
+impl FromStr for SortType {
+    type Err = TreeError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
         match string {
-            "ordinal" => SortType::Ordinal,
-            "categorical" => SortType::Categorical,
-            _ => {
-                panic!("Invalid choice for SortType")
-            }
+            "ordinal" => Ok(SortType::Ordinal),
+            "categorical" => Ok(SortType::Categorical),
+            _ => Err(TreeError::InvalidSortType(string.to_string())),
         }
     }
+}
 
+impl SortType {
     pub fn as_str(&self) -> &'static str {
         match self {
             SortType::Ordinal => "ordinal",
@@ -31,7 +38,7 @@ impl SortType {
     }
 }
 
-pub fn get_sort_type_for_dtype(dtype: &DataType) -> SortType {
+pub fn get_sort_type_for_dtype(column: &str, dtype: &DataType) -> Result<SortType, TreeError> {
     if matches!(
         dtype,
         DataType::Int8
@@ -44,11 +51,21 @@ pub fn get_sort_type_for_dtype(dtype: &DataType) -> SortType {
             | DataType::UInt64
             | DataType::Float32
             | DataType::Float64
+            | DataType::Date
+            | DataType::Datetime(_, _)
+            | DataType::Duration(_)
+            | DataType::Enum(_, _)
     ) {
-        return SortType::Ordinal;
+        return Ok(SortType::Ordinal);
     }
-    if matches!(dtype, DataType::String) {
-        return SortType::Categorical;
+    if matches!(
+        dtype,
+        DataType::String | DataType::Boolean | DataType::Categorical(_, _)
+    ) {
+        return Ok(SortType::Categorical);
     }
-    panic!("Unsupported DataType")
+    Err(TreeError::UnsupportedDtype {
+        column: column.to_string(),
+        dtype: dtype.clone(),
+    })
 }
@@ -0,0 +1,536 @@
+use crate::constants::internal_column;
+use crate::error::TreeError;
+use crate::old_preprocessing::{check_column_names, check_prediction_columns};
+use crate::regression_tree::{RegressionTree, PREDICTED_VALUE_COL};
+use crate::settings::{settings_methods, RegressionCriterion, Settings};
+use polars::prelude::{col, len, lit, Expr};
+use polars_core::datatypes::DataType;
+use polars_core::frame::DataFrame;
+use polars_core::prelude::{NamedFrom, Series};
+use polars_lazy::frame::{IntoLazy, LazyFrame};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Internal columns, the raw prediction of the ensemble and the rows a tree is fitted on:
+const SCORE_COL: &str = internal_column!("boosting_score");
+const SUBSAMPLE_COL: &str = internal_column!("subsample");
+
+// Probabilities are clipped to keep the log-loss finite:
+const EPSILON: f64 = 1e-15;
+
+/*
+Boosting loss governs what the trees are fitted on and what the ensemble predicts.
+Squared error predicts a value, every tree is fitted on the residuals y - F and a leaf predicts
+their mean.
+Log-loss predicts the probability of a 0/1 target, every tree is fitted on y - sigmoid(F), with F
+the log-odds. The splits come from the squared error on those residuals, but a leaf predicts the
+Newton step sum(y - p) / sum(p * (1 - p)) over its rows, as a mean residual would move the
+log-odds far less than the loss allows.
+*/
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BoostingLoss {
+    SquaredError,
+    LogLoss,
+}
+
+fn sigmoid(score: Expr) -> Expr {
+    lit(1.0) / (lit(1.0) + (lit(0.0) - score).exp())
+}
+
+impl BoostingLoss {
+    // The prediction before any tree is added, the best constant for this loss:
+    fn get_initial_score(&self, lf: &LazyFrame, target_column: &str) -> Result<f64, TreeError> {
+        let mean_df = lf
+            .clone()
+            .select([col(target_column).mean().alias(SCORE_COL)])
+            .collect()?;
+        let mean = mean_df
+            .column(SCORE_COL)?
+            .f64()?
+            .get(0)
+            .ok_or(TreeError::EmptyNode)?;
+        Ok(match self {
+            BoostingLoss::SquaredError => mean,
+            BoostingLoss::LogLoss => {
+                let mean = mean.clamp(EPSILON, 1.0 - EPSILON);
+                (mean / (1.0 - mean)).ln()
+            }
+        })
+    }
+
+    // The negative gradient of the loss with respect to the score:
+    fn get_pseudo_residual(&self, target_column: &str) -> Expr {
+        match self {
+            BoostingLoss::SquaredError => col(target_column) - col(SCORE_COL),
+            BoostingLoss::LogLoss => col(target_column) - sigmoid(col(SCORE_COL)),
+        }
+    }
+
+    // The value of a leaf over its rows, with the pseudo-residuals in place of the target. None
+    // keeps the mean pseudo-residual of the tree:
+    fn get_leaf_value(&self, target_column: &str) -> Option<Expr> {
+        match self {
+            BoostingLoss::SquaredError => None,
+            BoostingLoss::LogLoss => {
+                let probability = sigmoid(col(SCORE_COL));
+                let hessian = probability.clone() * (lit(1.0) - probability);
+                Some(col(target_column).sum() / hessian.sum())
+            }
+        }
+    }
+
+    fn get_prediction(&self, score: Expr) -> Expr {
+        match self {
+            BoostingLoss::SquaredError => score,
+            BoostingLoss::LogLoss => sigmoid(score),
+        }
+    }
+
+    fn get_mean_loss(
+        &self,
+        lf: &LazyFrame,
+        score: Expr,
+        target_column: &str,
+    ) -> Result<f64, TreeError> {
+        let target = col(target_column);
+        let loss = match self {
+            BoostingLoss::SquaredError => (target - score).pow(2),
+            BoostingLoss::LogLoss => {
+                let probability = sigmoid(score).clip(lit(EPSILON), lit(1.0 - EPSILON));
+                lit(0.0)
+                    - (target.clone() * probability.clone().log(std::f64::consts::E)
+                        + (lit(1.0) - target) * (lit(1.0) - probability).log(std::f64::consts::E))
+            }
+        };
+        let collected = lf
+            .clone()
+            .select([loss.mean().alias(SCORE_COL)])
+            .collect()?;
+        collected
+            .column(SCORE_COL)?
+            .f64()?
+            .get(0)
+            .ok_or(TreeError::EmptyNode)
+    }
+}
+
+/// An ensemble of shallow `RegressionTree`s, each fitted on the pseudo-residuals of the ones before.
+#[derive(Clone)]
+pub struct GradientBoostingModel {
+    trees: Vec<RegressionTree>,
+    initial_score: Option<f64>,
+    validation_losses: Vec<f64>,
+
+    // User defined settings:
+    loss: BoostingLoss,
+    learning_rate: f64,
+    n_rounds: usize,
+    subsample: f64,
+    early_stopping_rounds: Option<usize>,
+    seed: u64,
+    settings: Settings,
+}
+
+impl Default for GradientBoostingModel {
+    fn default() -> Self {
+        GradientBoostingBuilder::new().build()
+    }
+}
+
+impl GradientBoostingModel {
+    pub fn builder() -> GradientBoostingBuilder {
+        GradientBoostingBuilder::new()
+    }
+
+    pub fn get_settings(&self) -> Settings {
+        self.settings
+    }
+
+    pub fn get_trees(&self) -> &[RegressionTree] {
+        &self.trees
+    }
+
+    /// The loss on the validation frame after every round, empty if fitted without one.
+    pub fn get_validation_losses(&self) -> &[f64] {
+        &self.validation_losses
+    }
+
+    /// Fits the trees one round at a time, a validation frame enables early stopping.
+    pub fn fit(
+        &mut self,
+        lf: LazyFrame,
+        target_column: &str,
+        validation_lf: Option<&LazyFrame>,
+    ) -> Result<(), TreeError> {
+        if !(self.subsample > 0.0 && self.subsample <= 1.0) {
+            return Err(TreeError::InvalidSubsample(self.subsample));
+        }
+        let (train_lf, n_rows) = self.get_target_as_float(lf, target_column)?;
+        let initial_score = self.loss.get_initial_score(&train_lf, target_column)?;
+        // The score of the trees so far is kept next to the rows, every round only adds its tree:
+        let mut train_df = train_lf
+            .with_column(lit(initial_score).alias(SCORE_COL))
+            .collect()?;
+        let mut validation_df = match validation_lf {
+            Some(validation_lf) => Some(
+                self.get_target_as_float(validation_lf.clone(), target_column)?
+                    .0
+                    .with_column(lit(initial_score).alias(SCORE_COL))
+                    .collect()?,
+            ),
+            None => None,
+        };
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        self.trees = Vec::new();
+        self.validation_losses = Vec::new();
+        let mut best_round = 0;
+        for round in 0..self.n_rounds {
+            // Step 1: Take a subsample of the rows, and replace the target by the pseudo-residuals
+            // of their score. Collected once per round, as the tree scans these rows again for
+            // every node:
+            let mut in_subsample: Vec<bool> = (0..n_rows)
+                .map(|_| rng.gen::<f64>() < self.subsample)
+                .collect();
+            // A small frame can draw no rows at all, a tree needs at least one:
+            if !in_subsample.contains(&true) {
+                in_subsample[rng.gen_range(0..n_rows)] = true;
+            }
+            let round_df = train_df
+                .clone()
+                .lazy()
+                .with_column(lit(Series::new(SUBSAMPLE_COL.into(), in_subsample)))
+                .filter(col(SUBSAMPLE_COL))
+                .with_column(
+                    self.loss
+                        .get_pseudo_residual(target_column)
+                        .alias(target_column),
+                )
+                .drop([SUBSAMPLE_COL])
+                .collect()?;
+
+            // Step 2: Fit a tree on the pseudo-residuals, and set its leaves for the loss:
+            let mut settings = self.settings;
+            settings.set_seed(rng.gen());
+            let mut tree = RegressionTree::new(settings, RegressionCriterion::SquaredError);
+            tree.fit(round_df.clone().lazy().drop([SCORE_COL]), target_column)?;
+            if let Some(leaf_value) = self.loss.get_leaf_value(target_column) {
+                tree.set_leaf_values(round_df.lazy(), &leaf_value)?;
+            }
+
+            // Step 3: Add the shrunken value of the new tree to the scores:
+            let tree_score = lit(self.learning_rate) * tree.to_expr()?;
+            train_df = add_to_score(train_df, tree_score.clone())?;
+            if let Some(df) = validation_df.as_mut() {
+                *df = add_to_score(df.clone(), tree_score)?;
+            }
+            self.trees.push(tree);
+
+            // Step 4: Stop once the validation loss hasn't improved for a number of rounds:
+            if let Some(validation_df) = &validation_df {
+                let validation_loss = self.loss.get_mean_loss(
+                    &validation_df.clone().lazy(),
+                    col(SCORE_COL),
+                    target_column,
+                )?;
+                self.validation_losses.push(validation_loss);
+                if validation_loss < self.validation_losses[best_round] {
+                    best_round = round;
+                }
+                if let Some(early_stopping_rounds) = self.early_stopping_rounds {
+                    if round - best_round >= early_stopping_rounds {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Only keep the trees up to the best round on the validation frame:
+        if validation_df.is_some() && self.early_stopping_rounds.is_some() {
+            self.trees.truncate(best_round + 1);
+        }
+        self.initial_score = Some(initial_score);
+        Ok(())
+    }
+
+    /// Adds `PREDICTED_VALUE`, the value for squared error and the probability of 1 for log-loss.
+    pub fn predict(&self, lf: &LazyFrame) -> Result<LazyFrame, TreeError> {
+        let initial_score = self.initial_score.ok_or(TreeError::NotFitted)?;
+        check_prediction_columns(lf, &[PREDICTED_VALUE_COL.to_string()])?;
+        let score = self.get_score(initial_score)?;
+        Ok(lf
+            .clone()
+            .with_column(self.loss.get_prediction(score).alias(PREDICTED_VALUE_COL)))
+    }
+
+    // The raw prediction of the ensemble, the initial score plus the shrunken value of every tree:
+    fn get_score(&self, initial_score: f64) -> Result<Expr, TreeError> {
+        self.trees
+            .iter()
+            .try_fold(lit(initial_score), |score, tree| {
+                Ok(score + lit(self.learning_rate) * tree.to_expr()?)
+            })
+    }
+
+    // The rows with a target, cast to floats, and how many there are:
+    fn get_target_as_float(
+        &self,
+        lf: LazyFrame,
+        target_column: &str,
+    ) -> Result<(LazyFrame, usize), TreeError> {
+        // The target stays next to the internal columns, so it is checked as well:
+        let schema = lf.clone().collect_schema()?;
+        check_column_names(&schema)?;
+        let dtype = schema
+            .get(target_column)
+            .ok_or(TreeError::MissingTargetColumn(target_column.to_string()))?;
+        if !dtype.is_primitive_numeric() && *dtype != DataType::Boolean {
+            return Err(TreeError::UnsupportedDtype {
+                column: target_column.to_string(),
+                dtype: dtype.clone(),
+            });
+        }
+        let lf = lf
+            .filter(col(target_column).is_not_null())
+            .with_column(col(target_column).cast(DataType::Float64));
+
+        // Log-loss is only defined for a target of zeros and ones:
+        let target = col(target_column);
+        let summary_df = lf
+            .clone()
+            .select([
+                len().cast(DataType::UInt64).alias("n_rows"),
+                target
+                    .clone()
+                    .eq(lit(0.0))
+                    .or(target.eq(lit(1.0)))
+                    .all(true)
+                    .alias("is_binary"),
+            ])
+            .collect()?;
+        let n_rows = summary_df.column("n_rows")?.u64()?.get(0).unwrap_or(0) as usize;
+        if n_rows == 0 {
+            return Err(TreeError::EmptyNode);
+        }
+        let is_binary = summary_df.column("is_binary")?.bool()?.get(0) == Some(true);
+        if self.loss == BoostingLoss::LogLoss && !is_binary {
+            return Err(TreeError::NonBinaryTarget(target_column.to_string()));
+        }
+        Ok((lf, n_rows))
+    }
+}
+
+fn add_to_score(df: DataFrame, tree_score: Expr) -> Result<DataFrame, TreeError> {
+    Ok(df
+        .lazy()
+        .with_column((col(SCORE_COL) + tree_score).alias(SCORE_COL))
+        .collect()?)
+}
+
+/// Configures a `GradientBoostingModel` and the settings of its trees before it is fitted.
+#[derive(Clone, Copy)]
+pub struct GradientBoostingBuilder {
+    loss: BoostingLoss,
+    learning_rate: f64,
+    n_rounds: usize,
+    subsample: f64,
+    early_stopping_rounds: Option<usize>,
+    seed: u64,
+    settings: Settings,
+}
+
+impl Default for GradientBoostingBuilder {
+    fn default() -> Self {
+        // Boosting works best with shallow trees:
+        let mut settings = Settings::default();
+        settings.set_max_depth(3);
+        Self {
+            loss: BoostingLoss::SquaredError,
+            learning_rate: 0.1,
+            n_rounds: 100,
+            subsample: 1.0,
+            early_stopping_rounds: None,
+            seed: 0,
+            settings,
+        }
+    }
+}
+
+impl GradientBoostingBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn loss(mut self, loss: BoostingLoss) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn n_rounds(mut self, n_rounds: usize) -> Self {
+        self.n_rounds = n_rounds;
+        self
+    }
+
+    /// The share of rows, drawn anew every round, that a tree is fitted on. Should be above 0 and
+    /// at most 1, `fit` returns an error otherwise.
+    pub fn subsample(mut self, subsample: f64) -> Self {
+        self.subsample = subsample;
+        self
+    }
+
+    /// Stops once the validation loss hasn't improved for this many rounds.
+    pub fn early_stopping_rounds(mut self, early_stopping_rounds: usize) -> Self {
+        self.early_stopping_rounds = Some(early_stopping_rounds);
+        self
+    }
+
+    /// Seeds the subsamples and the features tried by the trees.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    settings_methods!();
+
+    pub fn build(self) -> GradientBoostingModel {
+        GradientBoostingModel {
+            trees: Vec::new(),
+            initial_score: None,
+            validation_losses: Vec::new(),
+            loss: self.loss,
+            learning_rate: self.learning_rate,
+            n_rounds: self.n_rounds,
+            subsample: self.subsample,
+            early_stopping_rounds: self.early_stopping_rounds,
+            seed: self.seed,
+            settings: self.settings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::get_raw_test_dataframe;
+    use polars::df;
+    use std::error::Error;
+
+    fn get_train_and_validation() -> (LazyFrame, LazyFrame) {
+        let lf = get_raw_test_dataframe().drop(["PassengerId", "Name", "Ticket", "Cabin"]);
+        (lf.clone().slice(0, 700), lf.slice(700, 191))
+    }
+
+    #[test]
+    fn test_fit_log_loss_with_early_stopping() -> Result<(), Box<dyn Error>> {
+        let (train_lf, validation_lf) = get_train_and_validation();
+        let mut model = GradientBoostingModel::builder()
+            .loss(BoostingLoss::LogLoss)
+            .n_rounds(4)
+            .learning_rate(0.5)
+            .max_depth(2)
+            .subsample(0.8)
+            .early_stopping_rounds(1)
+            .build();
+        assert!(matches!(
+            model.predict(&validation_lf),
+            Err(TreeError::NotFitted)
+        ));
+        model.fit(train_lf, "Survived", Some(&validation_lf))?;
+
+        // The trees beyond the best validation round are dropped:
+        let losses = model.get_validation_losses();
+        assert!(!losses.is_empty());
+        assert!(losses[0] < 0.66);
+        let best_round = (0..losses.len())
+            .min_by(|a, b| losses[*a].total_cmp(&losses[*b]))
+            .unwrap_or_default();
+        assert_eq!(model.get_trees().len(), best_round + 1);
+
+        let predicted = model.predict(&validation_lf)?.collect()?;
+        assert_eq!(predicted.height(), 191);
+        let probabilities = predicted.column(PREDICTED_VALUE_COL)?.f64()?;
+        assert!(probabilities
+            .into_no_null_iter()
+            .all(|probability| probability > 0.0 && probability < 1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_loss_leaves_take_a_newton_step() -> Result<(), Box<dyn Error>> {
+        // 2 of 10 positives where x is 0 and 8 of 10 where x is 1, so the initial probability is 0.5:
+        let df = df!(
+            "x" => [0, 1].repeat(10),
+            "target" => (0..20).map(|i| i32::from((i % 2 == 1) == (i < 16))).collect::<Vec<i32>>(),
+        )?;
+        let mut model = GradientBoostingModel::builder()
+            .loss(BoostingLoss::LogLoss)
+            .n_rounds(1)
+            .learning_rate(1.0)
+            .max_depth(1)
+            .min_leave_size(1)
+            .build();
+        model.fit(df.clone().lazy(), "target", None)?;
+
+        // The leaf of x = 1 is sum(y - p) / sum(p * (1 - p)) = 3 / 2.5, not the mean residual 0.3:
+        let predicted = model.predict(&df.lazy())?.collect()?;
+        assert_eq!(predicted.width(), 3);
+        let probabilities = predicted.column(PREDICTED_VALUE_COL)?.f64()?;
+        let expected = 1.0 / (1.0 + (-1.2f64).exp());
+        assert!((probabilities.get(1).unwrap_or_default() - expected).abs() < 1e-9);
+        assert!((probabilities.get(0).unwrap_or_default() - (1.0 - expected)).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_squared_error() -> Result<(), Box<dyn Error>> {
+        let (train_lf, validation_lf) = get_train_and_validation();
+        let mut model = GradientBoostingModel::builder()
+            .n_rounds(2)
+            .max_depth(1)
+            .build();
+        model.fit(train_lf, "Fare", Some(&validation_lf))?;
+
+        // Without early stopping all rounds are kept, and every round lowers the loss:
+        assert_eq!(model.get_trees().len(), 2);
+        let losses = model.get_validation_losses();
+        assert!(losses[1] < losses[0]);
+
+        let mut model = GradientBoostingModel::builder()
+            .loss(BoostingLoss::LogLoss)
+            .build();
+        let (train_lf, _) = get_train_and_validation();
+        assert!(matches!(
+            model.fit(train_lf, "Pclass", None),
+            Err(TreeError::NonBinaryTarget(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subsample() -> Result<(), Box<dyn Error>> {
+        let (train_lf, _) = get_train_and_validation();
+        for subsample in [0.0, -0.5, 1.5, f64::NAN] {
+            let mut model = GradientBoostingModel::builder()
+                .subsample(subsample)
+                .build();
+            assert!(matches!(
+                model.fit(train_lf.clone(), "Fare", None),
+                Err(TreeError::InvalidSubsample(_))
+            ));
+        }
+
+        // Far too few rows to expect one in the subsample, every round still fits a tree:
+        let mut model = GradientBoostingModel::builder()
+            .n_rounds(3)
+            .subsample(0.001)
+            .build();
+        model.fit(train_lf.slice(0, 20), "Fare", None)?;
+        assert_eq!(model.get_trees().len(), 3);
+        Ok(())
+    }
+}
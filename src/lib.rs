@@ -0,0 +1,36 @@
+mod classification_tree;
+mod codegen;
+mod constants;
+mod display_tree;
+#[cfg(test)]
+mod empty_tree;
+mod error;
+mod feature_encoding;
+mod feature_screening;
+mod filler_strings;
+mod gini_impurity;
+mod gradient_boosting;
+mod old_preprocessing;
+mod preprocessor;
+mod random_forest;
+mod regression_tree;
+mod serialization;
+mod settings;
+mod split_rule;
+mod sql;
+#[cfg(test)]
+mod test_utils;
+mod tree_node;
+
+pub use classification_tree::{ClassificationTree, ClassificationTreeBuilder, PruningStep};
+pub use display_tree::{BinaryTree, DisplayTree};
+pub use error::TreeError;
+pub use feature_screening::{ScreenedColumn, ScreeningReason};
+pub use gini_impurity::sort_type::SortType;
+pub use gradient_boosting::{BoostingLoss, GradientBoostingBuilder, GradientBoostingModel};
+pub use random_forest::{RandomForestClassifier, RandomForestClassifierBuilder, Voting};
+pub use regression_tree::{RegressionTree, RegressionTreeBuilder};
+pub use settings::{
+    CategoricalSearch, RegressionCriterion, Settings, SplitCriterion, ThresholdSearch, TreeGrowth,
+};
+pub use sql::SqlDialect;
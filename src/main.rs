@@ -1,15 +1,154 @@
+use clap::{Parser, Subcommand};
+use polars::io::SerReader;
+use polars::prelude::{CsvReadOptions, CsvWriter, SerWriter};
+use polars_lazy::frame::{IntoLazy, LazyFrame};
 use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use trees::ClassificationTree;
 
-mod classification_tree;
-mod constants;
-mod display_tree;
-mod empty_tree;
-mod filler_strings;
-mod gini_impurity;
-mod old_preprocessing;
-mod settings;
-mod test_utils;
+/// Fit, apply and inspect classification trees on csv files.
+#[derive(Parser)]
+#[command(name = "trees", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-fn main() -> Result<(), Box<dyn Error>> {
+#[derive(Subcommand)]
+enum Command {
+    /// Fit a tree on a csv file and save it as json.
+    Train {
+        #[arg(long)]
+        csv: PathBuf,
+        #[arg(long)]
+        target: String,
+        #[arg(long, default_value_t = 4)]
+        max_depth: u8,
+        #[arg(long, default_value_t = 32)]
+        min_leave_size: u128,
+        #[arg(long, default_value_t = 6)]
+        max_cardinality: u8,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Add the predicted label of a saved tree to a csv file.
+    Predict {
+        #[arg(long)]
+        model: PathBuf,
+        #[arg(long)]
+        csv: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Print a saved tree.
+    Show {
+        #[arg(long)]
+        model: PathBuf,
+    },
+}
+
+fn read_csv(path: &Path) -> Result<LazyFrame, Box<dyn Error>> {
+    let df = CsvReadOptions::default()
+        .try_into_reader_with_file_path(Some(path.into()))?
+        .finish()?;
+    Ok(df.lazy())
+}
+
+fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Train {
+            csv,
+            target,
+            max_depth,
+            min_leave_size,
+            max_cardinality,
+            out,
+        } => {
+            let mut tree = ClassificationTree::builder()
+                .max_depth(max_depth)
+                .min_leave_size(min_leave_size)
+                .max_cardinality(max_cardinality)
+                .build();
+            tree.fit(read_csv(&csv)?, &target)?;
+            tree.save_json(out)?;
+        }
+        Command::Predict { model, csv, out } => {
+            let tree = ClassificationTree::load_json(model)?;
+            let mut predicted = tree.predict(&read_csv(&csv)?)?.collect()?;
+            CsvWriter::new(File::create(out)?).finish(&mut predicted)?;
+        }
+        Command::Show { model } => {
+            let tree = ClassificationTree::load_json(model)?;
+            print!("{}", tree);
+        }
+    }
     Ok(())
 }
+
+fn main() -> Result<(), Box<dyn Error>> {
+    run(Cli::parse().command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_train_command() {
+        let cli = Cli::try_parse_from([
+            "trees",
+            "train",
+            "--csv",
+            "data.csv",
+            "--target",
+            "Survived",
+            "--max-depth",
+            "2",
+            "--out",
+            "model.json",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Train {
+                target,
+                max_depth,
+                min_leave_size,
+                ..
+            } => {
+                assert_eq!(target, "Survived");
+                assert_eq!(max_depth, 2);
+                assert_eq!(min_leave_size, 32);
+            }
+            _ => panic!("Expected the train command"),
+        }
+    }
+
+    #[test]
+    fn test_train_predict_and_show() -> Result<(), Box<dyn Error>> {
+        let directory = std::env::temp_dir().join("trees_cli_test");
+        std::fs::create_dir_all(&directory)?;
+        let model = directory.join("model.json");
+        let predictions = directory.join("predictions.csv");
+
+        run(Command::Train {
+            csv: PathBuf::from("Titanic-Dataset.csv"),
+            target: "Survived".to_string(),
+            max_depth: 1,
+            min_leave_size: 32,
+            max_cardinality: 6,
+            out: model.clone(),
+        })?;
+        run(Command::Predict {
+            model: model.clone(),
+            csv: PathBuf::from("Titanic-Dataset.csv"),
+            out: predictions.clone(),
+        })?;
+        run(Command::Show { model })?;
+
+        let predicted = read_csv(&predictions)?.collect()?;
+        assert_eq!(predicted.height(), 891);
+        assert!(predicted.column("PREDICTED_LABEL").is_ok());
+        Ok(())
+    }
+}
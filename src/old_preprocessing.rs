@@ -1,25 +1,92 @@
-use crate::constants::TARGET_COLUMN;
+use crate::constants::RESERVED_PREFIX;
+use crate::error::TreeError;
+use crate::feature_screening::{FeatureScreening, ScreenedColumn};
+use crate::preprocessor::Preprocessor;
 use crate::settings::Settings;
-
-use polars_core::prelude::NamedFrom;
-
-use crate::filler_strings::rename_filler_string_full_lazyframe;
+use polars_core::schema::Schema;
 use polars_lazy::frame::LazyFrame;
-use std::error::Error;
 
 pub const REDUNDANT_STRING_VALUE: &str = "FILLER_STRING";
 
-pub fn pre_process_dataframe(lf: LazyFrame, settings: Settings, target_column: &str) -> LazyFrame {
-    let mut lf = lf.rename([target_column], [TARGET_COLUMN], true);
-    lf = rename_filler_string_full_lazyframe(lf, settings).unwrap();
-    lf
+/// Drops the screened columns and collapses the rare strings, the returned preprocessor replays
+/// both on the frames to predict. All columns, the target included, keep their names.
+pub fn pre_process_dataframe(
+    lf: LazyFrame,
+    settings: Settings,
+    screening: &FeatureScreening,
+    target_column: &str,
+) -> Result<(LazyFrame, Preprocessor), TreeError> {
+    settings.validate()?;
+    check_input_columns(&lf, target_column)?;
+    // Screened on the columns as they are named by the user:
+    let screened_columns = screening.screen(&lf, target_column)?;
+    fit_preprocessor(lf, settings, screened_columns, target_column)
+}
+
+/// Like `pre_process_dataframe`, with the columns screened beforehand on another frame. A forest
+/// screens its full frame once, the repeated rows of a bootstrap sample would hide identifiers.
+pub(crate) fn pre_process_screened_dataframe(
+    lf: LazyFrame,
+    settings: Settings,
+    screened_columns: Vec<ScreenedColumn>,
+    target_column: &str,
+) -> Result<(LazyFrame, Preprocessor), TreeError> {
+    settings.validate()?;
+    check_input_columns(&lf, target_column)?;
+    fit_preprocessor(lf, settings, screened_columns, target_column)
+}
+
+fn check_input_columns(lf: &LazyFrame, target_column: &str) -> Result<(), TreeError> {
+    let schema = lf.clone().collect_schema()?;
+    if !schema.contains(target_column) {
+        return Err(TreeError::MissingTargetColumn(target_column.to_string()));
+    }
+    check_column_names(&schema)
+}
+
+fn fit_preprocessor(
+    lf: LazyFrame,
+    settings: Settings,
+    screened_columns: Vec<ScreenedColumn>,
+    target_column: &str,
+) -> Result<(LazyFrame, Preprocessor), TreeError> {
+    let preprocessor = Preprocessor::fit(&lf, settings, screened_columns, target_column)?;
+    Ok((preprocessor.transform(lf)?, preprocessor))
+}
+
+/// Internal columns are added next to the input columns, so these can't use the reserved prefix.
+pub(crate) fn check_column_names(schema: &Schema) -> Result<(), TreeError> {
+    let reserved_column = schema
+        .iter_names()
+        .find(|name| name.starts_with(RESERVED_PREFIX));
+    match reserved_column {
+        Some(name) => Err(TreeError::ReservedColumnName(name.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Predictions are added next to the input columns, so these can't share a name with them.
+pub(crate) fn check_prediction_columns(
+    lf: &LazyFrame,
+    prediction_columns: &[String],
+) -> Result<(), TreeError> {
+    let schema = lf.clone().collect_schema()?;
+    match prediction_columns
+        .iter()
+        .find(|column| schema.contains(column))
+    {
+        Some(column) => Err(TreeError::PredictionColumnExists(column.to_string())),
+        None => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::filler_strings::rename_filler_string_full_lazyframe;
     use crate::settings::Settings;
     use crate::test_utils::get_raw_test_dataframe;
+    use polars::prelude::{col, lit};
 
     #[test]
     fn test_rename_filler_strings() -> Result<(), Box<dyn std::error::Error>> {
@@ -29,7 +96,7 @@ mod tests {
         }
         let lf = get_raw_test_dataframe();
 
-        let renamed_lf = rename_filler_string_full_lazyframe(lf, Settings::default())?;
+        let renamed_lf = rename_filler_string_full_lazyframe(lf, Settings::default(), "Survived")?;
 
         let collected_lf = renamed_lf.collect()?;
         let unique_sex = collected_lf.column("Sex")?.n_unique()?;
@@ -37,4 +104,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_rename_filler_strings_with_max_cardinality() -> Result<(), Box<dyn std::error::Error>> {
+        let lf = get_raw_test_dataframe();
+
+        // Default settings keep "S", "C" and "Q", the two missing values stay missing:
+        let renamed_default =
+            rename_filler_string_full_lazyframe(lf.clone(), Settings::default(), "Survived")?
+                .collect()?;
+        assert_eq!(renamed_default.column("Embarked")?.n_unique()?, 4);
+
+        // A max cardinality of one keeps only the most common port, "S", next to filler and missing:
+        let mut settings = Settings::default();
+        settings.set_max_cardinality(1);
+        let renamed_lf = rename_filler_string_full_lazyframe(lf, settings, "Survived")?;
+        let collected = renamed_lf
+            .clone()
+            .filter(col("Embarked").neq(lit(REDUNDANT_STRING_VALUE)))
+            .select([col("Embarked")])
+            .unique(None, Default::default())
+            .collect()?;
+        assert_eq!(collected.height(), 1);
+        assert_eq!(collected.column("Embarked")?.str()?.get(0), Some("S"));
+        assert_eq!(renamed_lf.collect()?.column("Embarked")?.n_unique()?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pre_process_dataframe_uses_settings() -> Result<(), Box<dyn std::error::Error>> {
+        let lf = get_raw_test_dataframe();
+
+        // No value of "Sex" occurs 600 times, so the column is dropped:
+        let mut settings = Settings::default();
+        settings.set_min_leave_size(600);
+        let collected =
+            pre_process_dataframe(lf, settings, &FeatureScreening::default(), "Survived")?
+                .0
+                .collect()?;
+        assert!(collected.column("Sex").is_err());
+        assert!(collected.column("Survived").is_ok());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,209 @@
+use crate::classification_tree::INDEX_COL;
+use crate::error::TreeError;
+use crate::feature_encoding::FeatureEncoding;
+use crate::feature_screening::ScreenedColumn;
+use crate::filler_strings::get_prominent_strings;
+use crate::old_preprocessing::{check_column_names, REDUNDANT_STRING_VALUE};
+use crate::settings::Settings;
+use polars::prelude::{col, lit, when, JoinArgs, JoinType};
+use polars_core::datatypes::DataType;
+use polars_core::prelude::{NamedFrom, Series, SortMultipleOptions};
+use polars_lazy::frame::LazyFrame;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The string preprocessing learned during fit, replayed on every frame that is predicted.
+///
+/// Screened columns are dropped first, see `FeatureScreening`, then the features that are neither
+/// numeric nor strings are encoded, see `FeatureEncoding`.
+/// Values that were not prominent during fit, including the ones never seen, are replaced by
+/// the filler string, and the columns without any prominent value are dropped.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Preprocessor {
+    // Columns left out of the features before anything else is learned:
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    screened_columns: Vec<ScreenedColumn>,
+    // The encoding of every feature that isn't native:
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    encodings: BTreeMap<String, FeatureEncoding>,
+    // The kept values per string column:
+    kept_categories: BTreeMap<String, Vec<String>>,
+    dropped_columns: Vec<String>,
+}
+
+impl Preprocessor {
+    pub(crate) fn fit(
+        lf: &LazyFrame,
+        settings: Settings,
+        screened_columns: Vec<ScreenedColumn>,
+        target_column: &str,
+    ) -> Result<Self, TreeError> {
+        let mut preprocessor = Preprocessor {
+            screened_columns,
+            ..Preprocessor::default()
+        };
+        let lf = preprocessor.screen(lf.clone())?;
+        // The target is left as it is, e.g. a boolean target keeps its classes:
+        for (name, dtype) in lf.clone().collect_schema()?.iter() {
+            let encoding = FeatureEncoding::from_dtype(dtype);
+            if name.as_str() != target_column && !encoding.is_native() {
+                preprocessor.encodings.insert(name.to_string(), encoding);
+            }
+        }
+
+        let lf = preprocessor.encode(lf)?;
+        let schema = lf.clone().collect_schema()?;
+        for (name, dtype) in schema.iter() {
+            if *dtype != DataType::String {
+                continue;
+            }
+            match get_prominent_strings(&lf, name, settings)? {
+                Some(kept) => {
+                    preprocessor.kept_categories.insert(name.to_string(), kept);
+                }
+                None => preprocessor.dropped_columns.push(name.to_string()),
+            }
+        }
+        Ok(preprocessor)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.screened_columns.is_empty()
+            && self.encodings.is_empty()
+            && self.kept_categories.is_empty()
+            && self.dropped_columns.is_empty()
+    }
+
+    /// The encoding of a feature, `Native` for the ones that are split on as they are.
+    pub(crate) fn get_encoding(&self, column: &str) -> FeatureEncoding {
+        self.encodings.get(column).cloned().unwrap_or_default()
+    }
+
+    pub(crate) fn get_screened_columns(&self) -> &[ScreenedColumn] {
+        &self.screened_columns
+    }
+
+    fn screen(&self, lf: LazyFrame) -> Result<LazyFrame, TreeError> {
+        let schema = lf.clone().collect_schema()?;
+        let screened_columns: Vec<&str> = self
+            .screened_columns
+            .iter()
+            .map(|screened_column| screened_column.column.as_str())
+            .filter(|column| schema.contains(column))
+            .collect();
+        Ok(lf.drop(screened_columns))
+    }
+
+    fn encode(&self, lf: LazyFrame) -> Result<LazyFrame, TreeError> {
+        let schema = lf.clone().collect_schema()?;
+        let encoded_columns: Vec<_> = self
+            .encodings
+            .iter()
+            .filter(|(column, _)| schema.contains(column))
+            .map(|(column, encoding)| Ok(encoding.to_expr(column)?.alias(column)))
+            .collect::<Result<_, TreeError>>()?;
+        if encoded_columns.is_empty() {
+            return Ok(lf);
+        }
+        Ok(lf.with_columns(encoded_columns))
+    }
+
+    /// Columns seen during fit that are absent from the frame, e.g. the target, are skipped.
+    pub(crate) fn transform(&self, lf: LazyFrame) -> Result<LazyFrame, TreeError> {
+        let lf = self.encode(self.screen(lf)?)?;
+        let schema = lf.clone().collect_schema()?;
+        let renamed_columns: Vec<_> = self
+            .kept_categories
+            .iter()
+            .filter(|(column, _)| schema.contains(column))
+            .map(|(column, kept)| {
+                // Values that are actually missing stay missing:
+                let kept = Series::new(column.into(), kept);
+                when(
+                    col(column)
+                        .is_not_null()
+                        .and(col(column).is_in(lit(kept)).not()),
+                )
+                .then(lit(REDUNDANT_STRING_VALUE))
+                .otherwise(col(column))
+                .alias(column)
+            })
+            .collect();
+        let dropped_columns: Vec<&str> = self
+            .dropped_columns
+            .iter()
+            .filter(|column| schema.contains(column))
+            .map(|column| column.as_str())
+            .collect();
+
+        let mut lf = lf;
+        if !renamed_columns.is_empty() {
+            lf = lf.with_columns(renamed_columns);
+        }
+        Ok(lf.drop(dropped_columns))
+    }
+
+    /// Runs `predict` on the preprocessed rows and joins the prediction columns back onto the rows
+    /// as they were passed in, so that the user gets their own values back.
+    pub(crate) fn predict_rows(
+        &self,
+        lf: &LazyFrame,
+        prediction_columns: &[String],
+        predict: impl FnOnce(LazyFrame) -> Result<LazyFrame, TreeError>,
+    ) -> Result<LazyFrame, TreeError> {
+        let schema = lf.clone().collect_schema()?;
+        check_column_names(&schema)?;
+        let indexed_lf = lf.clone().with_row_index(INDEX_COL, None);
+        let prediction_lf = if self.is_empty() {
+            predict(indexed_lf)?
+        } else {
+            let mut selection = vec![col(INDEX_COL)];
+            selection.extend(prediction_columns.iter().map(col));
+            let predictions = predict(self.transform(indexed_lf.clone())?)?.select(selection);
+            indexed_lf
+                .drop(prediction_columns.iter().map(String::as_str))
+                .join(
+                    predictions,
+                    [col(INDEX_COL)],
+                    [col(INDEX_COL)],
+                    JoinArgs::new(JoinType::Left),
+                )
+        };
+        // Use the index column to get back the original ordering and then drop it:
+        Ok(prediction_lf
+            .sort([INDEX_COL], SortMultipleOptions::default())
+            .drop([INDEX_COL]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::get_raw_test_dataframe;
+    use std::error::Error;
+
+    #[test]
+    fn test_fit_and_transform_preprocessor() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+        let mut settings = Settings::default();
+        settings.set_max_cardinality(1);
+
+        let preprocessor = Preprocessor::fit(&lf, settings, Vec::new(), "Survived")?;
+        assert_eq!(
+            preprocessor.kept_categories.get("Embarked"),
+            Some(&vec!["S".to_string()])
+        );
+        assert!(preprocessor.dropped_columns.contains(&"Name".to_string()));
+
+        // Replayed on a frame without most of the columns seen during fit:
+        let transformed = preprocessor
+            .transform(lf.select([col("Embarked"), col("Name")]))?
+            .collect()?;
+        assert_eq!(transformed.get_column_names(), ["Embarked"]);
+        let embarked = transformed.column("Embarked")?.str()?;
+        assert_eq!(embarked.get(0), Some("S"));
+        assert_eq!(embarked.get(1), Some(REDUNDANT_STRING_VALUE));
+        assert_eq!(embarked.null_count(), 2);
+        Ok(())
+    }
+}
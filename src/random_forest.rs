@@ -0,0 +1,441 @@
+use crate::classification_tree::{ClassificationTree, PREDICTED_LABEL_COL, PROBABILITY_COL_PREFIX};
+use crate::error::TreeError;
+use crate::feature_screening::{screening_methods, FeatureScreening, ScreenedColumn};
+use crate::old_preprocessing::check_prediction_columns;
+use crate::settings::{settings_methods, Settings, SplitCriterion};
+use polars::prelude::IdxSize;
+use polars_core::datatypes::{DataType, IdxCa};
+use polars_core::frame::DataFrame;
+use polars_core::prelude::{NamedFrom, Series};
+use polars_lazy::frame::{IntoLazy, LazyFrame};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// How the predictions of the trees in a forest are combined.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Voting {
+    /// Every tree votes for its label, the most common label wins.
+    Majority,
+    /// The class probabilities are averaged over the trees, the most probable label wins.
+    Probability,
+}
+
+/// An ensemble of `ClassificationTree`s, each fitted on a bootstrap sample of the rows.
+///
+/// Unless `max_features` is set, every split tries the square root of the number of features.
+#[derive(Clone)]
+pub struct RandomForestClassifier {
+    trees: Vec<ClassificationTree>,
+    classes: Vec<String>,
+    oob_error: Option<f64>,
+    screened_columns: Vec<ScreenedColumn>,
+
+    // User defined settings, the settings of the trees get their own seed:
+    n_trees: usize,
+    voting: Voting,
+    settings: Settings,
+    screening: FeatureScreening,
+}
+
+impl Default for RandomForestClassifier {
+    fn default() -> Self {
+        Self::new(100, Voting::Majority, Settings::default())
+    }
+}
+
+// Index of the largest value, the first one on a tie so that votes follow the sorted classes:
+fn get_index_of_max(values: &[f64]) -> Option<usize> {
+    let mut best: Option<usize> = None;
+    for (index, value) in values.iter().enumerate() {
+        if best.is_none_or(|best| *value > values[best]) {
+            best = Some(index);
+        }
+    }
+    best
+}
+
+fn take_rows(df: &DataFrame, rows: Vec<IdxSize>) -> Result<DataFrame, TreeError> {
+    Ok(df.take(&IdxCa::from_vec("rows".into(), rows))?)
+}
+
+impl RandomForestClassifier {
+    pub fn new(n_trees: usize, voting: Voting, settings: Settings) -> Self {
+        Self {
+            trees: Vec::new(),
+            classes: Vec::new(),
+            oob_error: None,
+            screened_columns: Vec::new(),
+            n_trees,
+            voting,
+            settings,
+            screening: FeatureScreening::default(),
+        }
+    }
+
+    pub fn builder() -> RandomForestClassifierBuilder {
+        RandomForestClassifierBuilder::new()
+    }
+
+    pub fn get_settings(&self) -> Settings {
+        self.settings
+    }
+
+    pub fn get_trees(&self) -> &[ClassificationTree] {
+        &self.trees
+    }
+
+    pub fn get_classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    /// The share of rows misclassified by the trees that did not see them during fit.
+    pub fn get_oob_error(&self) -> Option<f64> {
+        self.oob_error
+    }
+
+    /// The columns that were left out of the features of every tree, with the reason why.
+    pub fn get_screened_columns(&self) -> &[ScreenedColumn] {
+        &self.screened_columns
+    }
+
+    pub fn fit(&mut self, lf: LazyFrame, target_column: &str) -> Result<(), TreeError> {
+        let df = lf.collect()?;
+        let n_rows = df.height();
+        if n_rows == 0 {
+            return Err(TreeError::EmptyNode);
+        }
+        let labels = df
+            .column(target_column)
+            .map_err(|_| TreeError::MissingTargetColumn(target_column.to_string()))?
+            .cast(&DataType::String)?;
+        let labels = labels.str()?;
+
+        // Screened once on all rows, the repeated rows of a bootstrap sample hide identifiers:
+        self.screened_columns = self.screening.screen(&df.clone().lazy(), target_column)?;
+        // Unless set, every split tries the square root of the number of features, so that the
+        // trees differ by more than their bootstrap sample:
+        let n_features = df.width() - 1 - self.screened_columns.len();
+        let max_features = self
+            .settings
+            .get_max_features()
+            .unwrap_or(((n_features as f64).sqrt() as usize).max(1));
+
+        let mut rng = StdRng::seed_from_u64(self.settings.get_seed());
+        let mut oob_votes: Vec<Vec<String>> = vec![Vec::new(); n_rows];
+        self.trees = Vec::with_capacity(self.n_trees);
+        for _ in 0..self.n_trees {
+            // Bootstrap sample, as many rows as there are drawn with replacement:
+            let drawn: Vec<IdxSize> = (0..n_rows)
+                .map(|_| rng.gen_range(0..n_rows) as IdxSize)
+                .collect();
+            let mut in_bag = vec![false; n_rows];
+            for row in drawn.iter() {
+                in_bag[*row as usize] = true;
+            }
+
+            let mut settings = self.settings;
+            settings.set_max_features(Some(max_features));
+            settings.set_seed(rng.gen());
+            let mut tree = ClassificationTree::new(settings);
+            tree.fit_screened(
+                take_rows(&df, drawn)?.lazy(),
+                target_column,
+                self.screened_columns.clone(),
+            )?;
+
+            // The rows that were not drawn are used to estimate the error:
+            let out_of_bag: Vec<IdxSize> = (0..n_rows)
+                .filter(|row| !in_bag[*row])
+                .map(|row| row as IdxSize)
+                .collect();
+            if !out_of_bag.is_empty() {
+                let predicted = tree
+                    .predict(&take_rows(&df, out_of_bag.clone())?.lazy())?
+                    .collect()?;
+                let predicted_labels = predicted.column(PREDICTED_LABEL_COL)?.str()?;
+                for (row, label) in out_of_bag.iter().zip(predicted_labels) {
+                    if let Some(label) = label {
+                        oob_votes[*row as usize].push(label.to_string());
+                    }
+                }
+            }
+            self.trees.push(tree);
+        }
+
+        let mut classes: Vec<String> = self
+            .trees
+            .iter()
+            .flat_map(|tree| tree.get_classes().to_vec())
+            .collect();
+        classes.sort();
+        classes.dedup();
+        self.classes = classes;
+
+        // Rows without a label or without an out-of-bag vote don't count:
+        let mut n_evaluated = 0;
+        let mut n_wrong = 0;
+        for (label, votes) in labels.into_iter().zip(oob_votes.iter()) {
+            if let (Some(label), Some(voted)) = (label, self.get_majority(votes)) {
+                n_evaluated += 1;
+                if label != voted {
+                    n_wrong += 1;
+                }
+            }
+        }
+        self.oob_error = (n_evaluated > 0).then(|| n_wrong as f64 / n_evaluated as f64);
+        Ok(())
+    }
+
+    fn get_majority(&self, votes: &[String]) -> Option<&str> {
+        let counts: Vec<f64> = self
+            .classes
+            .iter()
+            .map(|class| votes.iter().filter(|vote| *vote == class).count() as f64)
+            .collect();
+        if counts.iter().sum::<f64>() == 0.0 {
+            return None;
+        }
+        get_index_of_max(&counts).map(|index| self.classes[index].as_str())
+    }
+
+    pub fn predict(&self, lf: &LazyFrame) -> Result<LazyFrame, TreeError> {
+        if self.trees.is_empty() {
+            return Err(TreeError::NotFitted);
+        }
+        check_prediction_columns(lf, &[PREDICTED_LABEL_COL.to_string()])?;
+        let mut df = lf.clone().collect()?;
+        let predicted_labels: Vec<Option<String>> = match self.voting {
+            Voting::Majority => {
+                let mut votes: Vec<Vec<String>> = vec![Vec::new(); df.height()];
+                for tree in self.trees.iter() {
+                    let predicted = tree.predict(&df.clone().lazy())?.collect()?;
+                    let labels = predicted.column(PREDICTED_LABEL_COL)?.str()?;
+                    for (row_votes, label) in votes.iter_mut().zip(labels) {
+                        if let Some(label) = label {
+                            row_votes.push(label.to_string());
+                        }
+                    }
+                }
+                votes
+                    .iter()
+                    .map(|row_votes| self.get_majority(row_votes).map(str::to_string))
+                    .collect()
+            }
+            Voting::Probability => {
+                let probabilities = self.get_probabilities(&df)?;
+                (0..df.height())
+                    .map(|row| {
+                        let row_probabilities: Vec<f64> =
+                            probabilities.iter().map(|class| class[row]).collect();
+                        get_index_of_max(&row_probabilities)
+                            .map(|index| self.classes[index].clone())
+                    })
+                    .collect()
+            }
+        };
+        df.with_column(Series::new(PREDICTED_LABEL_COL.into(), predicted_labels))?;
+        Ok(df.lazy())
+    }
+
+    /// Adds one probability column per class, the average of the probabilities of the trees.
+    pub fn predict_proba(&self, lf: &LazyFrame) -> Result<LazyFrame, TreeError> {
+        if self.trees.is_empty() {
+            return Err(TreeError::NotFitted);
+        }
+        let probability_columns: Vec<String> = self
+            .classes
+            .iter()
+            .map(|class| format!("{}{}", PROBABILITY_COL_PREFIX, class))
+            .collect();
+        check_prediction_columns(lf, &probability_columns)?;
+        let mut df = lf.clone().collect()?;
+        let probabilities = self.get_probabilities(&df)?;
+        for (column, class_probabilities) in probability_columns.into_iter().zip(probabilities) {
+            df.with_column(Series::new(column.into(), class_probabilities))?;
+        }
+        Ok(df.lazy())
+    }
+
+    // Probabilities per class and row, a tree that never saw a class gives it zero:
+    fn get_probabilities(&self, df: &DataFrame) -> Result<Vec<Vec<f64>>, TreeError> {
+        let mut probabilities = vec![vec![0.0; df.height()]; self.classes.len()];
+        for tree in self.trees.iter() {
+            let predicted = tree.predict_proba(&df.clone().lazy())?.collect()?;
+            for (class, column) in tree
+                .get_classes()
+                .iter()
+                .zip(tree.get_probability_columns())
+            {
+                let Some(index) = self.classes.iter().position(|c| c == class) else {
+                    continue;
+                };
+                let tree_probabilities = predicted.column(&column)?.f64()?;
+                for (total, probability) in probabilities[index].iter_mut().zip(tree_probabilities)
+                {
+                    *total += probability.unwrap_or(0.0) / self.trees.len() as f64;
+                }
+            }
+        }
+        Ok(probabilities)
+    }
+}
+
+/// Configures a `RandomForestClassifier` and the settings of its trees before it is fitted.
+#[derive(Clone)]
+pub struct RandomForestClassifierBuilder {
+    n_trees: usize,
+    voting: Voting,
+    settings: Settings,
+    screening: FeatureScreening,
+}
+
+impl Default for RandomForestClassifierBuilder {
+    fn default() -> Self {
+        Self {
+            n_trees: 100,
+            voting: Voting::Majority,
+            settings: Settings::default(),
+            screening: FeatureScreening::default(),
+        }
+    }
+}
+
+impl RandomForestClassifierBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn n_trees(mut self, n_trees: usize) -> Self {
+        self.n_trees = n_trees;
+        self
+    }
+
+    pub fn voting(mut self, voting: Voting) -> Self {
+        self.voting = voting;
+        self
+    }
+
+    /// Seeds the bootstrap samples and the features tried, the same seed gives the same forest.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.settings.set_seed(seed);
+        self
+    }
+
+    settings_methods!();
+
+    pub fn split_criterion(mut self, split_criterion: SplitCriterion) -> Self {
+        self.settings.set_split_criterion(split_criterion);
+        self
+    }
+
+    screening_methods!();
+
+    pub fn build(self) -> RandomForestClassifier {
+        let mut forest = RandomForestClassifier::new(self.n_trees, self.voting, self.settings);
+        forest.screening = self.screening;
+        forest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::get_raw_test_dataframe;
+    use std::error::Error;
+
+    #[test]
+    fn test_fit_and_predict_random_forest() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+        let target_column = "Survived";
+        let builder = RandomForestClassifier::builder()
+            .n_trees(3)
+            .max_depth(2)
+            .max_features(3)
+            .seed(7);
+
+        let mut forest = builder.clone().build();
+        assert!(matches!(forest.predict(&lf), Err(TreeError::NotFitted)));
+        forest.fit(lf.clone(), target_column)?;
+        assert_eq!(forest.get_trees().len(), 3);
+        assert_eq!(forest.get_classes(), ["0", "1"]);
+
+        // Better than always predicting that a passenger died, which is wrong for 342 of 891:
+        let oob_error = forest.get_oob_error().ok_or("No out-of-bag error")?;
+        assert!(oob_error < 342.0 / 891.0);
+
+        let predicted = forest.predict(&lf)?.collect()?;
+        assert_eq!(predicted.height(), 891);
+        assert_eq!(predicted.column(PREDICTED_LABEL_COL)?.null_count(), 0);
+
+        let probabilities = forest.predict_proba(&lf)?.collect()?;
+        let total = probabilities.column("PROBABILITY_0")?.f64()?
+            + probabilities.column("PROBABILITY_1")?.f64()?;
+        assert!(total
+            .into_no_null_iter()
+            .all(|sum| (sum - 1.0).abs() < 1e-9));
+
+        // The same seed gives the same forest, also when voting on probabilities:
+        let mut refitted = builder.voting(Voting::Probability).build();
+        refitted.fit(lf.clone(), target_column)?;
+        assert_eq!(refitted.get_oob_error(), Some(oob_error));
+        let predicted_by_probability = refitted.predict(&lf)?.collect()?;
+        assert_eq!(predicted_by_probability.height(), 891);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_samples_features_by_default() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+        let mut forest = RandomForestClassifier::builder()
+            .n_trees(8)
+            .max_depth(1)
+            .seed(3)
+            .build();
+        forest.fit(lf, "Survived")?;
+
+        // Seven features are left after screening, every split tries two of them:
+        let mut root_features: Vec<String> = Vec::new();
+        for tree in forest.get_trees() {
+            assert_eq!(tree.get_settings().get_max_features(), Some(2));
+            let root = tree.to_string();
+            let feature = root.split_whitespace().next().unwrap_or_default();
+            root_features.push(feature.to_string());
+        }
+        root_features.sort();
+        root_features.dedup();
+        assert!(root_features.len() > 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_screens_identifiers_before_bootstrapping() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+        let mut forest = RandomForestClassifier::builder()
+            .n_trees(3)
+            .max_depth(3)
+            .seed(1)
+            .build();
+        forest.fit(lf.clone(), "Survived")?;
+        let is_passenger_id = |c: &ScreenedColumn| c.column == "PassengerId";
+        assert!(forest.get_screened_columns().iter().any(is_passenger_id));
+        for tree in forest.get_trees() {
+            assert!(tree.get_screened_columns().iter().any(is_passenger_id));
+            assert!(!tree.to_string().contains("PassengerId"));
+        }
+
+        // The columns chosen by the user are screened the same way for every tree:
+        let mut forest = RandomForestClassifier::builder()
+            .n_trees(2)
+            .max_depth(2)
+            .include_columns(["Pclass", "Age"])
+            .exclude_columns(["Age"])
+            .build();
+        forest.fit(lf, "Survived")?;
+        for tree in forest.get_trees() {
+            assert_eq!(tree.get_screened_columns(), forest.get_screened_columns());
+            let tree = tree.to_string();
+            assert!(!tree.contains("Age") && !tree.contains("Sex"));
+        }
+        Ok(())
+    }
+}
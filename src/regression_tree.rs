@@ -0,0 +1,574 @@
+use crate::constants::COUNT_COL;
+use crate::error::TreeError;
+use crate::feature_screening::{screening_methods, FeatureScreening, ScreenedColumn};
+use crate::gini_impurity::constants::GROUP_IMPURITY_COL;
+use crate::gini_impurity::regression_impurity::{
+    get_impurity_expression, get_leaf_value_expression, get_regression_impurity_for_all_columns,
+};
+use crate::old_preprocessing::{check_prediction_columns, pre_process_dataframe};
+use crate::preprocessor::Preprocessor;
+use crate::serialization::json_methods;
+use crate::settings::{settings_methods, RegressionCriterion, Settings};
+use crate::split_rule::SplitRule;
+use crate::tree_node::{GrowthState, NodePosition, TreeNode};
+use polars::prelude::{col, lit, Expr};
+use polars_core::datatypes::DataType;
+use polars_lazy::prelude::LazyFrame;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const PREDICTED_VALUE_COL: &str = "PREDICTED_VALUE";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RegressionTree {
+    // Generic tree properties:
+    left_node: Option<Box<RegressionTree>>,
+    right_node: Option<Box<RegressionTree>>,
+    depth: u8,
+    is_final: bool,
+
+    // Split of the node, the predicate is rebuilt from it as polars expressions can't be saved:
+    split_rule: Option<SplitRule>,
+    value: Option<f64>,
+
+    // Number of rows and impurity of the node, only used while fitting:
+    #[serde(skip)]
+    n_samples: u128,
+    #[serde(skip)]
+    impurity: f64,
+
+    // Columns the user asked to use or leave out, only kept by the root:
+    #[serde(default, skip_serializing_if = "FeatureScreening::is_default")]
+    screening: FeatureScreening,
+
+    // Preprocessing learned during fit, only kept by the root to replay it in predict:
+    #[serde(default, skip_serializing_if = "Preprocessor::is_empty")]
+    preprocessor: Preprocessor,
+
+    // User defined settings:
+    settings: Settings,
+    criterion: RegressionCriterion,
+}
+
+fn get_leaf_value(
+    lf: &LazyFrame,
+    target_column: &str,
+    criterion: RegressionCriterion,
+) -> Result<Option<f64>, TreeError> {
+    let value_df = lf
+        .clone()
+        .select([get_leaf_value_expression(target_column, criterion).cast(DataType::Float64)])
+        .collect()?;
+    let value = value_df.column(target_column)?.f64()?.get(0);
+    Ok(value)
+}
+
+impl Default for RegressionTree {
+    fn default() -> Self {
+        Self::new(Settings::default(), RegressionCriterion::SquaredError)
+    }
+}
+
+impl RegressionTree {
+    pub fn new(settings: Settings, criterion: RegressionCriterion) -> Self {
+        Self {
+            left_node: None,
+            right_node: None,
+            split_rule: None,
+            depth: 0,
+            is_final: false,
+            settings,
+            criterion,
+            value: None,
+            n_samples: 0,
+            impurity: 0.0,
+            screening: FeatureScreening::default(),
+            preprocessor: Preprocessor::default(),
+        }
+    }
+
+    pub fn builder() -> RegressionTreeBuilder {
+        RegressionTreeBuilder::new()
+    }
+
+    pub fn get_settings(&self) -> Settings {
+        self.settings
+    }
+
+    /// The columns that screening dropped before fit, see `ScreenedColumn`.
+    pub fn get_screened_columns(&self) -> &[ScreenedColumn] {
+        self.preprocessor.get_screened_columns()
+    }
+
+    pub fn get_criterion(&self) -> RegressionCriterion {
+        self.criterion
+    }
+
+    pub fn fit(&mut self, lf: LazyFrame, target_column: &str) -> Result<(), TreeError> {
+        // The target must be numeric to compute a mean or median, rows without it are left out:
+        let schema = lf.clone().collect_schema()?;
+        let lf = match schema.get(target_column) {
+            Some(dtype) if !dtype.is_primitive_numeric() => {
+                return Err(TreeError::UnsupportedDtype {
+                    column: target_column.to_string(),
+                    dtype: dtype.clone(),
+                });
+            }
+            Some(_) => lf.filter(col(target_column).is_not_null()),
+            None => lf,
+        };
+
+        // Pre-processing step: Screening the features and collapsing the rare strings.
+        let (lf, preprocessor) =
+            pre_process_dataframe(lf, self.settings, &self.screening, target_column)?;
+        if get_leaf_value(&lf, target_column, self.criterion)?.is_none() {
+            return Err(TreeError::EmptyNode);
+        }
+        self.grow(lf, target_column)?;
+        self.set_encodings(&preprocessor);
+        self.preprocessor = preprocessor;
+        Ok(())
+    }
+
+    pub fn predict(&self, lf: &LazyFrame) -> Result<LazyFrame, TreeError> {
+        if self.value.is_none() {
+            return Err(TreeError::NotFitted);
+        }
+        let prediction_columns = [PREDICTED_VALUE_COL.to_string()];
+        check_prediction_columns(lf, &prediction_columns)?;
+        // Add column for prediction:
+        let prediction_lf = lf
+            .clone()
+            .with_column(lit(f64::NAN).alias(PREDICTED_VALUE_COL));
+        // Predict value on the rows preprocessed as during fit:
+        self.preprocessor
+            .predict_rows(&prediction_lf, &prediction_columns, |lf| {
+                self.private_predict(lf, &|node| {
+                    Ok(vec![node.get_leaf_expr()?.alias(PREDICTED_VALUE_COL)])
+                })
+            })
+    }
+
+    /// The predicted value as one `when/then/otherwise` expression aliased to `PREDICTED_VALUE`,
+    /// which gradient boosting sums over its trees. Adding it with `with_column` equals `predict`.
+    pub fn to_expr(&self) -> Result<Expr, TreeError> {
+        if self.value.is_none() {
+            return Err(TreeError::NotFitted);
+        }
+        Ok(self.private_to_expr()?.alias(PREDICTED_VALUE_COL))
+    }
+
+    /// Replaces the value of every leaf by `value` aggregated over the rows of `lf` that reach it,
+    /// keeping the splits. Leaves without rows, or where `value` isn't finite, keep their value.
+    pub(crate) fn set_leaf_values(&mut self, lf: LazyFrame, value: &Expr) -> Result<(), TreeError> {
+        if self.value.is_none() {
+            return Err(TreeError::NotFitted);
+        }
+        if self.is_final {
+            let value_df = lf
+                .select([value
+                    .clone()
+                    .cast(DataType::Float64)
+                    .alias(PREDICTED_VALUE_COL)])
+                .collect()?;
+            let leaf_value = value_df.column(PREDICTED_VALUE_COL)?.f64()?.get(0);
+            self.value = leaf_value.filter(|value| value.is_finite()).or(self.value);
+            return Ok(());
+        }
+        let (left_lf, right_lf) = self.split_lazyframe_left_right(lf)?;
+        if let (Some(left), Some(right)) = (&mut self.left_node, &mut self.right_node) {
+            left.set_leaf_values(left_lf, value)?;
+            right.set_leaf_values(right_lf, value)?;
+        }
+        Ok(())
+    }
+
+    json_methods!();
+}
+
+impl TreeNode for RegressionTree {
+    fn get_split_rule(&self) -> Option<&SplitRule> {
+        self.split_rule.as_ref()
+    }
+
+    fn get_split_rule_mut(&mut self) -> Option<&mut SplitRule> {
+        self.split_rule.as_mut()
+    }
+
+    fn set_split_rule(&mut self, split_rule: SplitRule) {
+        self.split_rule = Some(split_rule);
+    }
+
+    fn get_child(&self, node_position: NodePosition) -> Option<&Self> {
+        match node_position {
+            NodePosition::Left => self.left_node.as_deref(),
+            NodePosition::Right => self.right_node.as_deref(),
+        }
+    }
+
+    fn get_child_mut(&mut self, node_position: NodePosition) -> Option<&mut Self> {
+        match node_position {
+            NodePosition::Left => self.left_node.as_deref_mut(),
+            NodePosition::Right => self.right_node.as_deref_mut(),
+        }
+    }
+
+    fn spawn_child(&mut self, node_position: NodePosition) -> Result<(), TreeError> {
+        let mut tree = RegressionTree::new(self.settings, self.criterion);
+        tree.depth = self.depth + 1;
+        tree.value = self.value;
+        tree.is_final = tree.depth >= tree.settings.get_max_depth();
+
+        match node_position {
+            NodePosition::Left => {
+                self.left_node = Some(Box::from(tree));
+            }
+            NodePosition::Right => {
+                self.right_node = Some(Box::from(tree));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_settings(&self) -> Settings {
+        self.settings
+    }
+
+    fn get_depth(&self) -> u8 {
+        self.depth
+    }
+
+    fn get_n_samples(&self) -> u128 {
+        self.n_samples
+    }
+
+    fn get_impurity(&self) -> f64 {
+        self.impurity
+    }
+
+    fn is_final(&self) -> bool {
+        self.is_final
+    }
+
+    fn set_final(&mut self) {
+        self.is_final = true;
+    }
+
+    fn get_leaf_expr(&self) -> Result<Expr, TreeError> {
+        Ok(lit(self.value.ok_or(TreeError::NotFitted)?))
+    }
+
+    // The impurity is measured as the split criterion does, an empty node keeps the value of
+    // its parent:
+    fn fit_leaf(&mut self, lf: &LazyFrame, target_column: &str) -> Result<(), TreeError> {
+        let leaf_df = lf
+            .clone()
+            .select([
+                get_leaf_value_expression(target_column, self.criterion)
+                    .cast(DataType::Float64)
+                    .alias(PREDICTED_VALUE_COL),
+                col(target_column)
+                    .count()
+                    .cast(DataType::UInt64)
+                    .alias(COUNT_COL),
+                get_impurity_expression(target_column, self.criterion)
+                    .cast(DataType::Float64)
+                    .alias(GROUP_IMPURITY_COL),
+            ])
+            .collect()?;
+        let value = leaf_df.column(PREDICTED_VALUE_COL)?.f64()?.get(0);
+        self.value = value.or(self.value);
+        self.n_samples = leaf_df.column(COUNT_COL)?.u64()?.get(0).unwrap_or(0) as u128;
+        self.impurity = leaf_df
+            .column(GROUP_IMPURITY_COL)?
+            .f64()?
+            .get(0)
+            .unwrap_or(0.0);
+        Ok(())
+    }
+
+    fn get_impurity_for_all_columns(
+        &self,
+        lf: LazyFrame,
+        state: &mut GrowthState,
+    ) -> Result<LazyFrame, TreeError> {
+        get_regression_impurity_for_all_columns(
+            lf,
+            state.target_column,
+            self.settings,
+            self.criterion,
+            &mut state.rng,
+        )
+    }
+}
+
+/// Configures the settings and criterion of a `RegressionTree` before it is fitted.
+#[derive(Clone)]
+pub struct RegressionTreeBuilder {
+    settings: Settings,
+    criterion: RegressionCriterion,
+    screening: FeatureScreening,
+}
+
+impl Default for RegressionTreeBuilder {
+    fn default() -> Self {
+        Self {
+            settings: Settings::default(),
+            criterion: RegressionCriterion::SquaredError,
+            screening: FeatureScreening::default(),
+        }
+    }
+}
+
+impl RegressionTreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    settings_methods!();
+
+    pub fn criterion(mut self, criterion: RegressionCriterion) -> Self {
+        self.criterion = criterion;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.settings.set_seed(seed);
+        self
+    }
+
+    screening_methods!();
+
+    pub fn build(self) -> RegressionTree {
+        let mut tree = RegressionTree::new(self.settings, self.criterion);
+        tree.screening = self.screening;
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{CategoricalSearch, TreeGrowth};
+    use crate::split_rule::SplitSelection;
+    use crate::test_utils::get_raw_test_dataframe;
+    use polars::df;
+    use polars::prelude::{when, NULL};
+    use polars_lazy::frame::IntoLazy;
+    use std::error::Error;
+
+    #[test]
+    fn test_fit_regression_tree_with_depth_0() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+
+        // A single leaf predicts the mean of the target:
+        let mut tree = RegressionTree::builder().max_depth(0).build();
+        tree.fit(lf.clone(), "Fare")?;
+        assert!(tree.is_final);
+        assert!((tree.value.unwrap() - 32.204208).abs() < 0.0001);
+
+        // Or the median, when minimizing the absolute error:
+        let mut tree = RegressionTree::builder()
+            .max_depth(0)
+            .criterion(RegressionCriterion::AbsoluteError)
+            .build();
+        tree.fit(lf, "Fare")?;
+        assert!((tree.value.unwrap() - 14.4542).abs() < 0.0001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_regression_tree_with_depth_1() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+
+        let mut tree = RegressionTree::builder().max_depth(1).build();
+        tree.fit(lf.clone(), "Fare")?;
+
+        assert!(!tree.is_final);
+        let left_value = tree.left_node.as_ref().unwrap().value.unwrap();
+        let right_value = tree.right_node.as_ref().unwrap().value.unwrap();
+        assert_ne!(left_value, right_value);
+
+        // An input column with the name of the prediction is not overwritten:
+        let lf = lf.with_column(col("Fare").alias(PREDICTED_VALUE_COL));
+        assert!(matches!(
+            tree.predict(&lf),
+            Err(TreeError::PredictionColumnExists(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_regression_tree_rejects_string_target() {
+        let lf = get_raw_test_dataframe();
+        let mut tree = RegressionTree::default();
+        assert!(tree.predict(&lf).is_err());
+        assert!(matches!(
+            tree.fit(lf, "Sex"),
+            Err(TreeError::UnsupportedDtype { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fit_regression_tree_skips_missing_targets() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe().with_column(
+            when(col("Age").lt(lit(30)))
+                .then(lit(NULL))
+                .otherwise(col("Fare"))
+                .alias("Fare"),
+        );
+
+        // The rows without a target change nothing, as if they were never there:
+        let mut tree = RegressionTree::builder().max_depth(2).build();
+        tree.fit(lf.clone(), "Fare")?;
+        let mut expected_tree = RegressionTree::builder().max_depth(2).build();
+        expected_tree.fit(lf.clone().filter(col("Fare").is_not_null()), "Fare")?;
+
+        let predicted = tree.predict(&lf)?.collect()?;
+        let expected = expected_tree.predict(&lf)?.collect()?;
+        assert!(predicted.equals_missing(&expected));
+        Ok(())
+    }
+
+    fn get_n_leaves(tree: &RegressionTree) -> usize {
+        match (tree.left_node.as_deref(), tree.right_node.as_deref()) {
+            (Some(left), Some(right)) => get_n_leaves(left) + get_n_leaves(right),
+            _ => 1,
+        }
+    }
+
+    #[test]
+    fn test_regression_tree_stopping_rules() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+        let builder = RegressionTree::builder().max_depth(3);
+
+        // No split lowers the variance of "Fare", about 2466, by this much:
+        let mut tree = builder.clone().min_impurity_decrease(2500.0).build();
+        tree.fit(lf.clone(), "Fare")?;
+        assert_eq!(get_n_leaves(&tree), 1);
+
+        let mut tree = builder.clone().min_samples_split(1000).build();
+        tree.fit(lf.clone(), "Fare")?;
+        assert_eq!(get_n_leaves(&tree), 1);
+
+        let mut tree = builder.clone().max_leaf_nodes(3).build();
+        tree.fit(lf.clone(), "Fare")?;
+        assert_eq!(get_n_leaves(&tree), 3);
+
+        let mut tree = builder
+            .max_leaf_nodes(5)
+            .tree_growth(TreeGrowth::BestFirst)
+            .build();
+        tree.fit(lf.clone(), "Fare")?;
+        assert_eq!(get_n_leaves(&tree), 5);
+        let predicted = tree.predict(&lf)?.collect()?;
+        assert_eq!(
+            predicted.column(PREDICTED_VALUE_COL)?.is_nan()?.sum(),
+            Some(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_regression_tree_samples_features() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+
+        // With a single feature tried, the seed decides which one the root splits on:
+        let mut root_features: Vec<String> = Vec::new();
+        for seed in 0..6 {
+            let mut tree = RegressionTree::builder()
+                .max_depth(1)
+                .max_features(1)
+                .seed(seed)
+                .build();
+            tree.fit(lf.clone(), "Fare")?;
+            root_features.push(tree.split_rule.as_ref().unwrap().feature.clone());
+        }
+        root_features.sort();
+        root_features.dedup();
+        assert!(root_features.len() > 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_regression_tree_with_category_subsets() -> Result<(), Box<dyn Error>> {
+        // One category against the rest never separates the low from the high values:
+        let letters = ["A", "B", "C", "D"].map(|letter| vec![letter; 25]).concat();
+        let values = [1.0, 2.0, 11.0, 12.0].map(|value| vec![value; 25]).concat();
+        let lf = df!("Letter" => letters, "Value" => values)?.lazy();
+
+        let mut tree = RegressionTree::builder()
+            .max_depth(1)
+            .min_leave_size(1)
+            .categorical_search(CategoricalSearch::Subsets)
+            .build();
+        tree.fit(lf.clone(), "Value")?;
+        let split_rule = tree.split_rule.as_ref().unwrap();
+        assert_eq!(
+            split_rule.selection,
+            SplitSelection::Categories(vec!["A".to_string(), "B".to_string()])
+        );
+
+        let predicted = tree.predict(&lf)?.collect()?;
+        let predicted = predicted.column(PREDICTED_VALUE_COL)?.f64()?;
+        assert_eq!(predicted.get(0), Some(1.5));
+        assert_eq!(predicted.get(99), Some(11.5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_regression_tree_depth_2() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+
+        let mut tree = RegressionTree::builder().max_depth(2).build();
+        tree.fit(lf.clone(), "Fare")?;
+
+        let collected = tree.predict(&lf)?.collect()?;
+        assert_eq!(collected.height(), 891);
+        let n_null = collected.column(PREDICTED_VALUE_COL)?.is_nan()?.sum();
+        assert_eq!(n_null, Some(0));
+        assert_eq!(collected.column(PREDICTED_VALUE_COL)?.null_count(), 0);
+
+        // Predictions keep the original row ordering:
+        let passenger_ids = tree
+            .predict(&lf)?
+            .select([col("PassengerId")])
+            .collect()?
+            .column("PassengerId")?
+            .i64()?
+            .into_no_null_iter()
+            .collect::<Vec<i64>>();
+        assert_eq!(passenger_ids, (1..=891).collect::<Vec<i64>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_expr_matches_predict() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe();
+
+        let mut tree = RegressionTree::builder().max_depth(3).build();
+        assert!(matches!(tree.to_expr(), Err(TreeError::NotFitted)));
+        tree.fit(lf.clone(), "Fare")?;
+
+        let predicted = tree.predict(&lf)?.collect()?;
+        let compiled = lf.with_column(tree.to_expr()?).collect()?;
+        assert!(predicted.equals_missing(&compiled));
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load_regression_tree() -> Result<(), Box<dyn Error>> {
+        let lf = get_raw_test_dataframe().select([col("Pclass"), col("Sex"), col("Fare")]);
+        let mut tree = RegressionTree::builder().max_depth(2).build();
+        tree.fit(lf.clone(), "Fare")?;
+
+        let path = std::env::temp_dir().join("trees_regression_tree.json");
+        tree.save_json(&path)?;
+        let loaded = RegressionTree::load_json(&path)?;
+        assert_eq!(loaded.get_criterion(), tree.get_criterion());
+
+        let expected = tree.predict(&lf)?.collect()?;
+        let predicted = loaded.predict(&lf)?.collect()?;
+        assert!(expected.equals_missing(&predicted));
+        Ok(())
+    }
+}
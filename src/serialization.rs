@@ -0,0 +1,60 @@
+use crate::error::TreeError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// Bump this whenever the saved structure of a tree changes. Version 2 added the preprocessing,
+// the feature encodings, the screening and the statistics used for pruning, version 3 saves
+// the type of a threshold:
+pub(crate) const FORMAT_VERSION: u32 = 3;
+
+#[derive(Serialize)]
+struct SavedTree<'a, T> {
+    format_version: u32,
+    tree: &'a T,
+}
+
+// The tree is only read once the version is known to match:
+#[derive(Deserialize)]
+struct LoadedTree {
+    format_version: u32,
+    tree: serde_json::Value,
+}
+
+// Adds `save_json` and `load_json` to the impl block of a tree:
+macro_rules! json_methods {
+    () => {
+        /// Saves the fitted tree as json, together with the version of the format.
+        pub fn save_json(&self, path: impl AsRef<std::path::Path>) -> Result<(), TreeError> {
+            crate::serialization::save_json(self, path)
+        }
+
+        /// Loads a tree saved by `save_json`, a tree saved in another version of the format is
+        /// rejected.
+        pub fn load_json(path: impl AsRef<std::path::Path>) -> Result<Self, TreeError> {
+            crate::serialization::load_json(path)
+        }
+    };
+}
+pub(crate) use json_methods;
+
+pub(crate) fn save_json<T: Serialize>(tree: &T, path: impl AsRef<Path>) -> Result<(), TreeError> {
+    let writer = BufWriter::new(File::create(path)?);
+    let saved = SavedTree {
+        format_version: FORMAT_VERSION,
+        tree,
+    };
+    serde_json::to_writer(writer, &saved)?;
+    Ok(())
+}
+
+pub(crate) fn load_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, TreeError> {
+    let reader = BufReader::new(File::open(path)?);
+    let loaded: LoadedTree = serde_json::from_reader(reader)?;
+    if loaded.format_version != FORMAT_VERSION {
+        return Err(TreeError::UnsupportedFormatVersion(loaded.format_version));
+    }
+    Ok(serde_json::from_value(loaded.tree)?)
+}
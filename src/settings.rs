@@ -1,8 +1,179 @@
-#[derive(Clone, Copy)]
+use crate::error::TreeError;
+use serde::{Deserialize, Serialize};
+
+/*
+Split criterion governs how the impurity of a candidate split is scored.
+Gini is 1 - sum(p^2), entropy is -sum(p * log2(p)) and log-loss is -sum(p * ln(p)).
+Entropy and log-loss differ only by a constant factor, so they select the same splits.
+*/
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SplitCriterion {
+    Gini,
+    Entropy,
+    LogLoss,
+}
+
+impl SplitCriterion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SplitCriterion::Gini => "gini",
+            SplitCriterion::Entropy => "entropy",
+            SplitCriterion::LogLoss => "log_loss",
+        }
+    }
+}
+
+/*
+Regression criterion governs how the impurity of a candidate split is scored for a numeric target,
+and which statistic of the target is predicted in a leaf.
+*/
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum RegressionCriterion {
+    SquaredError,
+    AbsoluteError,
+}
+
+impl RegressionCriterion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegressionCriterion::SquaredError => "squared_error",
+            RegressionCriterion::AbsoluteError => "absolute_error",
+        }
+    }
+}
+
+/*
+Threshold search governs which thresholds are tried when splitting an ordinal column.
+Quantiles tries the boundaries of the given number of equally sized bins, exhaustive tries the
+boundary between every pair of neighbouring distinct values.
+The exhaustive search is a single sorted pass over the distinct values, except for the absolute
+error, as a median can't be accumulated. That pairs every row with every threshold instead.
+*/
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ThresholdSearch {
+    Quantiles(u16),
+    Exhaustive,
+}
+
+/*
+Categorical search governs which groups of categories are tried when splitting a categorical column.
+One-vs-rest puts a single category in the left group, subsets can put several categories there.
+For a regression tree, the subsets are the prefixes of the categories sorted by their mean or
+median target, which contain the best subset for the squared error.
+*/
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum CategoricalSearch {
+    OneVsRest,
+    Subsets,
+}
+
+/*
+Tree growth governs the order in which the nodes of a tree are split.
+Depth-first grows the left subtree completely before the right one, best-first always splits the
+leaf with the largest decrease in impurity next, which matters once max_leaf_nodes is reached.
+*/
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum TreeGrowth {
+    #[default]
+    DepthFirst,
+    BestFirst,
+}
+
+// Adds the setters of the settings shared by all trees to the impl block of a builder with a
+// `settings` field:
+macro_rules! settings_methods {
+    () => {
+        pub fn max_depth(mut self, max_depth: u8) -> Self {
+            self.settings.set_max_depth(max_depth);
+            self
+        }
+
+        pub fn min_leave_size(mut self, min_leave_size: u128) -> Self {
+            self.settings.set_min_leave_size(min_leave_size);
+            self
+        }
+
+        pub fn max_cardinality(mut self, max_cardinality: u8) -> Self {
+            self.settings.set_max_cardinality(max_cardinality);
+            self
+        }
+
+        pub fn threshold_search(
+            mut self,
+            threshold_search: crate::settings::ThresholdSearch,
+        ) -> Self {
+            self.settings.set_threshold_search(threshold_search);
+            self
+        }
+
+        pub fn categorical_search(
+            mut self,
+            categorical_search: crate::settings::CategoricalSearch,
+        ) -> Self {
+            self.settings.set_categorical_search(categorical_search);
+            self
+        }
+
+        /// Tries only this many randomly chosen features at every split.
+        pub fn max_features(mut self, max_features: usize) -> Self {
+            self.settings.set_max_features(Some(max_features));
+            self
+        }
+
+        /// Only splits a node if this lowers its impurity by at least this much.
+        pub fn min_impurity_decrease(mut self, min_impurity_decrease: f64) -> Self {
+            self.settings
+                .set_min_impurity_decrease(min_impurity_decrease);
+            self
+        }
+
+        /// Only splits a node with at least this many rows.
+        pub fn min_samples_split(mut self, min_samples_split: u128) -> Self {
+            self.settings.set_min_samples_split(min_samples_split);
+            self
+        }
+
+        pub fn max_leaf_nodes(mut self, max_leaf_nodes: usize) -> Self {
+            self.settings.set_max_leaf_nodes(Some(max_leaf_nodes));
+            self
+        }
+
+        pub fn tree_growth(mut self, tree_growth: crate::settings::TreeGrowth) -> Self {
+            self.settings.set_tree_growth(tree_growth);
+            self
+        }
+    };
+}
+pub(crate) use settings_methods;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Settings {
     max_depth: u8,
     min_leave_size: u128,
     max_cardinality: u8,
+    split_criterion: SplitCriterion,
+    threshold_search: ThresholdSearch,
+    categorical_search: CategoricalSearch,
+    // Number of randomly chosen features tried at every split, all features are tried if None:
+    #[serde(default)]
+    max_features: Option<usize>,
+    #[serde(default)]
+    seed: u64,
+    // Stopping rules of a tree, on top of the max depth and min leave size:
+    #[serde(default)]
+    min_impurity_decrease: f64,
+    #[serde(default)]
+    min_samples_split: u128,
+    #[serde(default)]
+    max_leaf_nodes: Option<usize>,
+    #[serde(default)]
+    tree_growth: TreeGrowth,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new(4, 32, 6)
+    }
 }
 
 impl Settings {
@@ -11,13 +182,18 @@ impl Settings {
             max_depth,
             min_leave_size,
             max_cardinality,
+            split_criterion: SplitCriterion::Gini,
+            threshold_search: ThresholdSearch::Quantiles(10),
+            categorical_search: CategoricalSearch::OneVsRest,
+            max_features: None,
+            seed: 0,
+            min_impurity_decrease: 0.0,
+            min_samples_split: 2,
+            max_leaf_nodes: None,
+            tree_growth: TreeGrowth::DepthFirst,
         }
     }
 
-    pub fn default() -> Self {
-        Self::new(4, 32, 6)
-    }
-
     pub fn set_max_depth(&mut self, max_depth: u8) {
         self.max_depth = max_depth;
     }
@@ -37,4 +213,89 @@ impl Settings {
     pub fn get_max_cardinality(&self) -> u8 {
         self.max_cardinality
     }
+
+    pub fn set_max_cardinality(&mut self, max_cardinality: u8) {
+        self.max_cardinality = max_cardinality;
+    }
+
+    pub fn get_split_criterion(&self) -> SplitCriterion {
+        self.split_criterion
+    }
+
+    pub fn set_split_criterion(&mut self, split_criterion: SplitCriterion) {
+        self.split_criterion = split_criterion;
+    }
+
+    pub fn get_threshold_search(&self) -> ThresholdSearch {
+        self.threshold_search
+    }
+
+    pub fn set_threshold_search(&mut self, threshold_search: ThresholdSearch) {
+        self.threshold_search = threshold_search;
+    }
+
+    /// Checks the settings that can't be used to fit, before any work is done.
+    pub(crate) fn validate(&self) -> Result<(), TreeError> {
+        match self.threshold_search {
+            // Fewer than two bins have no boundary to try:
+            ThresholdSearch::Quantiles(n) if n < 2 => Err(TreeError::InvalidQuantiles(n)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_categorical_search(&self) -> CategoricalSearch {
+        self.categorical_search
+    }
+
+    pub fn set_categorical_search(&mut self, categorical_search: CategoricalSearch) {
+        self.categorical_search = categorical_search;
+    }
+
+    pub fn get_max_features(&self) -> Option<usize> {
+        self.max_features
+    }
+
+    pub fn set_max_features(&mut self, max_features: Option<usize>) {
+        self.max_features = max_features;
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn get_min_impurity_decrease(&self) -> f64 {
+        self.min_impurity_decrease
+    }
+
+    pub fn set_min_impurity_decrease(&mut self, min_impurity_decrease: f64) {
+        self.min_impurity_decrease = min_impurity_decrease;
+    }
+
+    pub fn get_min_samples_split(&self) -> u128 {
+        self.min_samples_split
+    }
+
+    pub fn set_min_samples_split(&mut self, min_samples_split: u128) {
+        self.min_samples_split = min_samples_split;
+    }
+
+    pub fn get_max_leaf_nodes(&self) -> Option<usize> {
+        self.max_leaf_nodes
+    }
+
+    pub fn set_max_leaf_nodes(&mut self, max_leaf_nodes: Option<usize>) {
+        self.max_leaf_nodes = max_leaf_nodes;
+    }
+
+    pub fn get_tree_growth(&self) -> TreeGrowth {
+        self.tree_growth
+    }
+
+    pub fn set_tree_growth(&mut self, tree_growth: TreeGrowth) {
+        self.tree_growth = tree_growth;
+    }
 }
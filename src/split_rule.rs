@@ -0,0 +1,511 @@
+use crate::error::TreeError;
+use crate::feature_encoding::FeatureEncoding;
+use crate::gini_impurity::constants::{
+    FEATURE_COLUMN_NAME, MISSING_LEFT_COL, SELECTION_COLUMN, SORT_TYPE_COL,
+    SPLIT_INTEGER_THRESHOLD_COL, SPLIT_THRESHOLD_COL, SPLIT_UNSIGNED_THRESHOLD_COL,
+};
+use crate::gini_impurity::sort_type::SortType;
+use crate::sql::SqlDialect;
+use polars::prelude::{lit, Expr};
+use polars_core::datatypes::DataType;
+use polars_core::frame::DataFrame;
+use polars_core::prelude::{NamedFrom, Series};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The rows that go to the left child: above a threshold, or in a set of categories.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SplitSelection {
+    Threshold(Threshold),
+    Categories(Vec<String>),
+}
+
+/// A threshold in the type of the feature, so that large integers are compared exactly.
+///
+/// Serialized with its type, so that a threshold is read back in the type it was saved in.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Threshold {
+    Integer(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+impl Threshold {
+    pub(crate) fn to_lit(self) -> Expr {
+        match self {
+            Threshold::Integer(threshold) => lit(threshold),
+            Threshold::Unsigned(threshold) => lit(threshold),
+            Threshold::Float(threshold) => lit(threshold),
+        }
+    }
+
+    /// The largest integer that isn't above the threshold, saturated at the bounds of an i64.
+    pub(crate) fn floor(self) -> i64 {
+        match self {
+            Threshold::Integer(threshold) => threshold,
+            Threshold::Unsigned(threshold) => i64::try_from(threshold).unwrap_or(i64::MAX),
+            Threshold::Float(threshold) => threshold.floor() as i64,
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::Integer(threshold) => write!(f, "{}", threshold),
+            Threshold::Unsigned(threshold) => write!(f, "{}", threshold),
+            Threshold::Float(threshold) => write!(f, "{}", threshold),
+        }
+    }
+}
+
+/// A fitted split kept as plain data, so that a tree can be saved and its predicate rebuilt.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SplitRule {
+    pub(crate) feature: String,
+    pub(crate) sort_type: SortType,
+    pub(crate) selection: SplitSelection,
+    pub(crate) missing_left: bool,
+    // Set after fit, the impurity computations only see the encoded feature:
+    #[serde(default, skip_serializing_if = "FeatureEncoding::is_native")]
+    pub(crate) encoding: FeatureEncoding,
+}
+
+impl SplitRule {
+    /// Reads the best split, a single row frame as returned by the impurity computations.
+    pub(crate) fn from_best_split(collected: &DataFrame) -> Result<Self, TreeError> {
+        // Extract all relevant values:
+        let feature = collected
+            .column(FEATURE_COLUMN_NAME)?
+            .str()?
+            .get(0)
+            .ok_or(TreeError::EmptyNode)?;
+        let sort_type = SortType::from_str(
+            collected
+                .column(SORT_TYPE_COL)?
+                .str()?
+                .get(0)
+                .ok_or(TreeError::EmptyNode)?,
+        )?;
+        let missing_left = collected
+            .column(MISSING_LEFT_COL)?
+            .bool()?
+            .get(0)
+            .ok_or(TreeError::EmptyNode)?;
+
+        // An ordinal split has a threshold, a categorical split has its categories:
+        let selection = match sort_type {
+            SortType::Ordinal => match get_threshold(collected)? {
+                Some(threshold) => SplitSelection::Threshold(threshold),
+                None => {
+                    return Err(TreeError::InvalidSplit(format!(
+                        "{} should hold a finite threshold",
+                        SPLIT_THRESHOLD_COL
+                    )))
+                }
+            },
+            SortType::Categorical => {
+                let selection = collected.column(SELECTION_COLUMN)?.list()?.get_as_series(0);
+                let categories: Vec<String> = match selection {
+                    Some(selection) => selection
+                        .str()?
+                        .into_no_null_iter()
+                        .map(|category| category.to_string())
+                        .collect(),
+                    None => Vec::new(),
+                };
+                if categories.is_empty() {
+                    return Err(TreeError::InvalidSplit(format!(
+                        "{} should hold at least one category",
+                        SELECTION_COLUMN
+                    )));
+                }
+                SplitSelection::Categories(categories)
+            }
+        };
+        Ok(Self {
+            feature: feature.to_string(),
+            sort_type,
+            selection,
+            missing_left,
+            encoding: FeatureEncoding::Native,
+        })
+    }
+
+    /// The predicate holds on the input rows as well as on the encoded ones.
+    pub(crate) fn to_expr(&self) -> Result<Expr, TreeError> {
+        let column_name = self.feature.as_str();
+        let column = self.encoding.to_expr(column_name)?;
+        let predicate = match &self.selection {
+            SplitSelection::Threshold(threshold) => column.gt(threshold.to_lit()),
+            SplitSelection::Categories(categories) => {
+                if let [category] = categories.as_slice() {
+                    column.eq(lit(category.clone()))
+                } else {
+                    column.is_in(lit(Series::new(column_name.into(), categories)))
+                }
+            }
+        };
+        // Rows with a missing value follow the direction learned during fit:
+        Ok(predicate.fill_null(lit(self.missing_left)))
+    }
+
+    /// The condition of a `CASE WHEN` clause that holds for the rows going to the left child.
+    ///
+    /// Durations and datetimes with a time zone have no portable literal and can't be exported.
+    pub(crate) fn to_sql(&self, dialect: SqlDialect) -> Result<String, TreeError> {
+        let column = dialect.quote_identifier(&self.feature);
+        // Filler strings are never selected, so rare and unseen categories end up in the `ELSE`:
+        let condition = match (&self.selection, &self.encoding) {
+            (SplitSelection::Threshold(threshold), FeatureEncoding::Enum { .. }) => {
+                // Unknown categories are missing, like they are in `to_expr`:
+                let (below, above) = self
+                    .encoding
+                    .split_categories(*threshold)
+                    .unwrap_or_default();
+                if self.missing_left {
+                    let below = quote_strings(below, dialect);
+                    return Ok(format!("({column} IS NULL OR {column} NOT IN ({below}))"));
+                }
+                format!("{} IN ({})", column, quote_strings(above, dialect))
+            }
+            (SplitSelection::Threshold(threshold), FeatureEncoding::Date) => {
+                let date = self
+                    .encoding
+                    .format_threshold(*threshold)
+                    .unwrap_or_default();
+                format!("{} > {}", column, dialect.date_literal(&date))
+            }
+            (
+                SplitSelection::Threshold(threshold),
+                FeatureEncoding::Datetime {
+                    time_zone: None, ..
+                },
+            ) => {
+                let datetime = self
+                    .encoding
+                    .format_threshold(*threshold)
+                    .unwrap_or_default();
+                format!("{} > {}", column, dialect.datetime_literal(&datetime))
+            }
+            (
+                SplitSelection::Threshold(_),
+                FeatureEncoding::Datetime {
+                    time_unit,
+                    time_zone: Some(time_zone),
+                },
+            ) => {
+                return Err(TreeError::UnsupportedDtype {
+                    column: self.feature.clone(),
+                    dtype: DataType::Datetime((*time_unit).into(), Some(time_zone.into())),
+                });
+            }
+            (SplitSelection::Threshold(_), FeatureEncoding::Duration { time_unit }) => {
+                return Err(TreeError::UnsupportedDtype {
+                    column: self.feature.clone(),
+                    dtype: DataType::Duration((*time_unit).into()),
+                });
+            }
+            (SplitSelection::Threshold(threshold), _) => format!("{} > {}", column, threshold),
+            (SplitSelection::Categories(categories), FeatureEncoding::Boolean) => {
+                let values: Vec<String> = categories
+                    .iter()
+                    .map(|category| dialect.boolean_literal(category == "true"))
+                    .collect();
+                if let [value] = values.as_slice() {
+                    format!("{} = {}", column, value)
+                } else {
+                    format!("{} IN ({})", column, values.join(", "))
+                }
+            }
+            (SplitSelection::Categories(categories), _) => {
+                if let [category] = categories.as_slice() {
+                    format!("{} = {}", column, dialect.quote_string(category))
+                } else {
+                    format!("{} IN ({})", column, quote_strings(categories, dialect))
+                }
+            }
+        };
+        // A comparison with NULL is never true, so missing values only need a clause to go left:
+        if self.missing_left {
+            Ok(format!("({} IS NULL OR {})", column, condition))
+        } else {
+            Ok(condition)
+        }
+    }
+
+    /// The condition of an `if` statement in the generated Rust code, on the given field of `row`.
+    pub(crate) fn to_rust(&self, field_name: &str) -> String {
+        let field = format!("row.{}", field_name);
+        // Integer thresholds lie between two observed values, so these fit the type of the field.
+        // Float thresholds are compared as floats:
+        match (&self.selection, &self.encoding) {
+            (SplitSelection::Threshold(threshold), FeatureEncoding::Enum { categories }) => {
+                // The position in the declared order, unknown categories are missing:
+                let (position, threshold) = match threshold {
+                    Threshold::Float(threshold) => ("position as f64", format!("{:?}", threshold)),
+                    _ => ("position as i64", threshold.to_string()),
+                };
+                format!(
+                    "{}.as_deref().and_then(|value| [{}].iter().position(|category| *category == value)).map_or({}, |position| ({}) > {})",
+                    field,
+                    debug_strings(categories, ", "),
+                    self.missing_left,
+                    position,
+                    threshold
+                )
+            }
+            (SplitSelection::Threshold(Threshold::Float(threshold)), _) => format!(
+                "{}.map_or({}, |value| (value as f64) > {:?})",
+                field, self.missing_left, threshold
+            ),
+            (SplitSelection::Threshold(threshold), _) => format!(
+                "{}.map_or({}, |value| value > {})",
+                field, self.missing_left, threshold
+            ),
+            (SplitSelection::Categories(categories), FeatureEncoding::Boolean) => {
+                let values: Vec<&str> = categories
+                    .iter()
+                    .map(|category| if category == "true" { "true" } else { "false" })
+                    .collect();
+                format!(
+                    "{}.map_or({}, |value| matches!(value, {}))",
+                    field,
+                    self.missing_left,
+                    values.join(" | ")
+                )
+            }
+            (SplitSelection::Categories(categories), _) => format!(
+                "{}.as_deref().map_or({}, |value| matches!(value, {}))",
+                field,
+                self.missing_left,
+                debug_strings(categories, " | ")
+            ),
+        }
+    }
+}
+
+// Only the column of the type of the feature holds its threshold:
+fn get_threshold(collected: &DataFrame) -> Result<Option<Threshold>, TreeError> {
+    if let Some(threshold) = collected.column(SPLIT_INTEGER_THRESHOLD_COL)?.i64()?.get(0) {
+        return Ok(Some(Threshold::Integer(threshold)));
+    }
+    if let Some(threshold) = collected
+        .column(SPLIT_UNSIGNED_THRESHOLD_COL)?
+        .u64()?
+        .get(0)
+    {
+        return Ok(Some(Threshold::Unsigned(threshold)));
+    }
+    let threshold = collected.column(SPLIT_THRESHOLD_COL)?.f64()?.get(0);
+    Ok(threshold
+        .filter(|threshold| threshold.is_finite())
+        .map(Threshold::Float))
+}
+
+fn quote_strings(values: &[String], dialect: SqlDialect) -> String {
+    let quoted: Vec<String> = values
+        .iter()
+        .map(|value| dialect.quote_string(value))
+        .collect();
+    quoted.join(", ")
+}
+
+fn debug_strings(values: &[String], separator: &str) -> String {
+    let debugged: Vec<String> = values.iter().map(|value| format!("{:?}", value)).collect();
+    debugged.join(separator)
+}
+
+// Two decimals are enough to read a tree, trailing zeros are left out. Integers are kept whole:
+fn format_threshold(threshold: Threshold) -> String {
+    let Threshold::Float(threshold) = threshold else {
+        return threshold.to_string();
+    };
+    let formatted = format!("{:.2}", threshold);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+impl fmt::Display for SplitRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.selection {
+            SplitSelection::Threshold(threshold) => {
+                // Dates, datetimes, durations and enums are shown in their own type:
+                let threshold = self
+                    .encoding
+                    .format_threshold(*threshold)
+                    .unwrap_or_else(|| format_threshold(*threshold));
+                write!(f, "{} > {}", self.feature, threshold)
+            }
+            SplitSelection::Categories(categories) => {
+                if let [category] = categories.as_slice() {
+                    write!(f, "{} == {}", self.feature, category)
+                } else {
+                    write!(f, "{} in [{}]", self.feature, categories.join(", "))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feature_encoding::FeatureTimeUnit;
+    use polars_core::df;
+    use polars_lazy::frame::IntoLazy;
+
+    #[test]
+    fn test_split_rule_from_best_split() -> Result<(), Box<dyn std::error::Error>> {
+        let collected = df![
+            FEATURE_COLUMN_NAME => &["Embarked"],
+            SORT_TYPE_COL => &["categorical"],
+            SELECTION_COLUMN => [Series::new("".into(), ["Q", "S"])],
+            MISSING_LEFT_COL => &[true],
+        ]?;
+        let split_rule = SplitRule::from_best_split(&collected)?;
+        assert_eq!(
+            split_rule.selection,
+            SplitSelection::Categories(vec!["Q".to_string(), "S".to_string()])
+        );
+        assert!(split_rule.missing_left);
+        assert_eq!(split_rule.to_string(), "Embarked in [Q, S]");
+
+        let split_rule = SplitRule {
+            feature: "Fare".to_string(),
+            sort_type: SortType::Ordinal,
+            selection: SplitSelection::Threshold(Threshold::Float(21.6792)),
+            missing_left: false,
+            encoding: FeatureEncoding::Native,
+        };
+        assert_eq!(split_rule.to_string(), "Fare > 21.68");
+        assert_eq!(split_rule.to_sql(SqlDialect::Ansi)?, "\"Fare\" > 21.6792");
+
+        let split_rule = SplitRule {
+            feature: "Embarked".to_string(),
+            sort_type: SortType::Categorical,
+            selection: SplitSelection::Categories(vec!["Q".to_string(), "S".to_string()]),
+            missing_left: true,
+            encoding: FeatureEncoding::Native,
+        };
+        assert_eq!(
+            split_rule.to_sql(SqlDialect::MySql)?,
+            "(`Embarked` IS NULL OR `Embarked` IN ('Q', 'S'))"
+        );
+        assert_eq!(
+            split_rule.to_rust("embarked"),
+            "row.embarked.as_deref().map_or(true, |value| matches!(value, \"Q\" | \"S\"))"
+        );
+
+        let collected = df![
+            FEATURE_COLUMN_NAME => &["Fare"],
+            SORT_TYPE_COL => &["ordinal"],
+            SPLIT_THRESHOLD_COL => &[f64::NAN],
+            SPLIT_INTEGER_THRESHOLD_COL => &[None::<i64>],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            MISSING_LEFT_COL => &[false],
+        ]?;
+        assert!(matches!(
+            SplitRule::from_best_split(&collected),
+            Err(TreeError::InvalidSplit(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoded_split_rules() -> Result<(), Box<dyn std::error::Error>> {
+        let split_rule = SplitRule {
+            feature: "Class".to_string(),
+            sort_type: SortType::Ordinal,
+            selection: SplitSelection::Threshold(Threshold::Integer(0)),
+            missing_left: true,
+            encoding: FeatureEncoding::Enum {
+                categories: vec!["3".to_string(), "2".to_string(), "1".to_string()],
+            },
+        };
+        assert_eq!(split_rule.to_string(), "Class > 3");
+        assert_eq!(
+            split_rule.to_sql(SqlDialect::Ansi)?,
+            "(\"Class\" IS NULL OR \"Class\" NOT IN ('3'))"
+        );
+
+        let split_rule = SplitRule {
+            feature: "Booked".to_string(),
+            sort_type: SortType::Ordinal,
+            selection: SplitSelection::Threshold(Threshold::Integer(19723)),
+            missing_left: false,
+            encoding: FeatureEncoding::Date,
+        };
+        assert_eq!(split_rule.to_string(), "Booked > 2024-01-01");
+        assert_eq!(
+            split_rule.to_sql(SqlDialect::SqlServer)?,
+            "[Booked] > CAST('2024-01-01' AS DATE)"
+        );
+
+        let split_rule = SplitRule {
+            encoding: FeatureEncoding::Duration {
+                time_unit: FeatureTimeUnit::Milliseconds,
+            },
+            ..split_rule
+        };
+        assert!(matches!(
+            split_rule.to_sql(SqlDialect::Ansi),
+            Err(TreeError::UnsupportedDtype { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_threshold_is_exact() -> Result<(), Box<dyn std::error::Error>> {
+        // Above 2^53, neighbouring integers are the same float:
+        let threshold = (1_i64 << 60) + 1;
+        let collected = df![
+            FEATURE_COLUMN_NAME => &["Id"],
+            SORT_TYPE_COL => &["ordinal"],
+            SPLIT_THRESHOLD_COL => &[None::<f64>],
+            SPLIT_INTEGER_THRESHOLD_COL => &[threshold],
+            SPLIT_UNSIGNED_THRESHOLD_COL => &[None::<u64>],
+            MISSING_LEFT_COL => &[false],
+        ]?;
+        let split_rule = SplitRule::from_best_split(&collected)?;
+        assert_eq!(
+            split_rule.selection,
+            SplitSelection::Threshold(Threshold::Integer(threshold))
+        );
+        assert_eq!(split_rule.to_string(), "Id > 1152921504606846977");
+        assert_eq!(
+            split_rule.to_sql(SqlDialect::Ansi)?,
+            "\"Id\" > 1152921504606846977"
+        );
+        assert_eq!(
+            split_rule.to_rust("id"),
+            "row.id.map_or(false, |value| value > 1152921504606846977)"
+        );
+
+        let predicted = df!["Id" => [threshold, threshold + 1]]?
+            .lazy()
+            .select([split_rule.to_expr()?])
+            .collect()?;
+        let predicted: Vec<Option<bool>> = predicted.column("Id")?.bool()?.into_iter().collect();
+        assert_eq!(predicted, [Some(false), Some(true)]);
+
+        // The threshold is saved with its type, a whole float stays a float:
+        let json = serde_json::to_string(&split_rule.selection)?;
+        assert_eq!(json, "{\"threshold\":{\"integer\":1152921504606846977}}");
+        assert_eq!(
+            serde_json::from_str::<SplitSelection>(&json)?,
+            split_rule.selection
+        );
+        for threshold in [Threshold::Unsigned(5), Threshold::Float(21.0)] {
+            let selection = SplitSelection::Threshold(threshold);
+            let json = serde_json::to_string(&selection)?;
+            assert_eq!(serde_json::from_str::<SplitSelection>(&json)?, selection);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// The flavour of SQL that a tree is exported to, it decides how names and strings are quoted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SqlDialect {
+    /// Double quoted identifiers, as used by PostgreSQL, SQLite, Snowflake and polars.
+    #[default]
+    Ansi,
+    /// Backtick quoted identifiers and backslash escapes in strings.
+    MySql,
+    /// Identifiers in square brackets.
+    SqlServer,
+}
+
+impl SqlDialect {
+    pub(crate) fn quote_identifier(&self, identifier: &str) -> String {
+        match self {
+            SqlDialect::Ansi => format!("\"{}\"", identifier.replace('"', "\"\"")),
+            SqlDialect::MySql => format!("`{}`", identifier.replace('`', "``")),
+            SqlDialect::SqlServer => format!("[{}]", identifier.replace(']', "]]")),
+        }
+    }
+
+    pub(crate) fn quote_string(&self, value: &str) -> String {
+        let value = match self {
+            // MySQL treats a backslash in a string literal as an escape character:
+            SqlDialect::MySql => value.replace('\\', "\\\\"),
+            SqlDialect::Ansi | SqlDialect::SqlServer => value.to_string(),
+        };
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    // SQL Server has no boolean literals, its bit columns hold 1 and 0:
+    pub(crate) fn boolean_literal(&self, value: bool) -> String {
+        match (self, value) {
+            (SqlDialect::SqlServer, true) => "1".to_string(),
+            (SqlDialect::SqlServer, false) => "0".to_string(),
+            (_, true) => "TRUE".to_string(),
+            (_, false) => "FALSE".to_string(),
+        }
+    }
+
+    /// A date written as `YYYY-MM-DD`.
+    pub(crate) fn date_literal(&self, date: &str) -> String {
+        match self {
+            SqlDialect::SqlServer => format!("CAST({} AS DATE)", self.quote_string(date)),
+            SqlDialect::Ansi | SqlDialect::MySql => format!("DATE {}", self.quote_string(date)),
+        }
+    }
+
+    /// A datetime without a time zone written as `YYYY-MM-DD HH:MM:SS[.fraction]`.
+    pub(crate) fn datetime_literal(&self, datetime: &str) -> String {
+        match self {
+            SqlDialect::SqlServer => format!("CAST({} AS DATETIME2)", self.quote_string(datetime)),
+            SqlDialect::Ansi | SqlDialect::MySql => {
+                format!("TIMESTAMP {}", self.quote_string(datetime))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(
+            SqlDialect::Ansi.quote_identifier("a \"b\""),
+            "\"a \"\"b\"\"\""
+        );
+        assert_eq!(SqlDialect::MySql.quote_identifier("a`b"), "`a``b`");
+        assert_eq!(SqlDialect::SqlServer.quote_identifier("a]b"), "[a]]b]");
+        assert_eq!(SqlDialect::Ansi.quote_string("O'Brien"), "'O''Brien'");
+        assert_eq!(SqlDialect::MySql.quote_string("a\\'b"), "'a\\\\''b'");
+        assert_eq!(SqlDialect::SqlServer.boolean_literal(true), "1");
+        assert_eq!(
+            SqlDialect::SqlServer.date_literal("2024-01-01"),
+            "CAST('2024-01-01' AS DATE)"
+        );
+        assert_eq!(
+            SqlDialect::Ansi.datetime_literal("2024-01-01 12:00:00"),
+            "TIMESTAMP '2024-01-01 12:00:00'"
+        );
+    }
+}
@@ -1,8 +1,10 @@
+use crate::feature_screening::FeatureScreening;
+use crate::gini_impurity::constants::SELECTION_COLUMN;
 use crate::old_preprocessing::pre_process_dataframe;
 use crate::settings::Settings;
 use polars::io::SerReader;
 use polars::prelude::CsvReadOptions;
-use polars_core::prelude::{DataFrame, DataType};
+use polars_core::prelude::{DataFrame, DataType, Series};
 use polars_lazy::frame::{IntoLazy, LazyFrame};
 
 // This allows the test module to access the functions in the outer scope
@@ -20,7 +22,23 @@ pub fn get_raw_test_dataframe() -> LazyFrame {
 
 pub fn get_preprocessed_test_dataframe() -> LazyFrame {
     let raw_lf = get_raw_test_dataframe();
-    pre_process_dataframe(raw_lf, Settings::default(), TITANIC_TARGET_COLUMN)
+    pre_process_dataframe(
+        raw_lf,
+        Settings::default(),
+        &FeatureScreening::default(),
+        TITANIC_TARGET_COLUMN,
+    )
+    .unwrap()
+    .0
+}
+
+/// The selection column of an ordinal best split, which has no categories.
+pub fn get_ordinal_selection_column() -> Series {
+    Series::full_null(
+        SELECTION_COLUMN.into(),
+        1,
+        &DataType::List(Box::new(DataType::String)),
+    )
 }
 
 pub fn assert_single_row_df_equal(
@@ -35,18 +53,39 @@ pub fn assert_single_row_df_equal(
 
         match dtype {
             DataType::Float64 => {
-                let val1 = series1.f64()?.get(0).unwrap();
-                let val2 = series2.f64()?.get(0).unwrap();
-
-                assert!(
-                    (val1 - val2).abs() < 0.00001f64,
-                    "{}",
-                    format!("left: {}, right: {}", val1, val2)
-                );
+                // Missing values only equal each other:
+                match (series1.f64()?.get(0), series2.f64()?.get(0)) {
+                    (Some(val1), Some(val2)) => assert!(
+                        (val1 - val2).abs() < 0.00001f64,
+                        "{}",
+                        format!("left: {}, right: {}", val1, val2)
+                    ),
+                    (val1, val2) => assert_eq!(val1, val2),
+                }
             }
+            DataType::Int64 => assert_eq!(series1.i64()?.get(0), series2.i64()?.get(0)),
+            DataType::UInt64 => assert_eq!(series1.u64()?.get(0), series2.u64()?.get(0)),
             DataType::String => {
-                let val1 = series1.str()?.get(0).unwrap();
-                let val2 = series2.str()?.get(0).unwrap();
+                let val1 = series1.str()?.get(0);
+                let val2 = series2.str()?.get(0);
+                assert_eq!(val1, val2);
+            }
+            DataType::List(_) => {
+                let val1 = series1.list()?.get_as_series(0);
+                let val2 = series2.list()?.get_as_series(0);
+                assert_eq!(val1.is_some(), val2.is_some());
+                if let (Some(val1), Some(val2)) = (val1, val2) {
+                    assert!(
+                        val1.equals_missing(&val2),
+                        "left: {}, right: {}",
+                        val1,
+                        val2
+                    );
+                }
+            }
+            DataType::Boolean => {
+                let val1 = series1.bool()?.get(0).unwrap();
+                let val2 = series2.bool()?.get(0).unwrap();
                 assert_eq!(val1, val2);
             }
             _ => {
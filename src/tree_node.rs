@@ -0,0 +1,331 @@
+use crate::error::TreeError;
+use crate::gini_impurity::constants::{
+    NORMALIZED_CHILD_GINI, TOTAL_LEFT_GROUP_COL, TOTAL_RIGHT_GROUP_COL,
+};
+use crate::preprocessor::Preprocessor;
+use crate::settings::{Settings, TreeGrowth};
+use crate::split_rule::SplitRule;
+use polars::prelude::{not, when, Expr, UnionArgs};
+use polars_core::frame::DataFrame;
+use polars_lazy::dsl::concat;
+use polars_lazy::prelude::LazyFrame;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Clone, Copy)]
+pub(crate) enum NodePosition {
+    Left,
+    Right,
+}
+
+pub(crate) fn get_size_of_left_and_right(collected: &DataFrame) -> Result<(u128, u128), TreeError> {
+    let size_left = collected
+        .column(TOTAL_LEFT_GROUP_COL)?
+        .f64()?
+        .get(0)
+        .ok_or(TreeError::EmptyNode)? as u128;
+    let size_right = collected
+        .column(TOTAL_RIGHT_GROUP_COL)?
+        .f64()?
+        .get(0)
+        .ok_or(TreeError::EmptyNode)? as u128;
+    Ok((size_left, size_right))
+}
+
+pub(crate) fn get_child_impurity(collected: &DataFrame) -> Result<f64, TreeError> {
+    collected
+        .column(NORMALIZED_CHILD_GINI)?
+        .f64()?
+        .get(0)
+        .ok_or(TreeError::EmptyNode)
+}
+
+// Shared by all nodes while fitting, the nodes are fitted one after the other:
+pub(crate) struct GrowthState<'a> {
+    pub(crate) target_column: &'a str,
+    pub(crate) rng: StdRng,
+    n_leaves: usize,
+}
+
+impl GrowthState<'_> {
+    fn is_leaf_budget_spent(&self, settings: Settings) -> bool {
+        settings
+            .get_max_leaf_nodes()
+            .is_some_and(|max_leaf_nodes| self.n_leaves >= max_leaf_nodes)
+    }
+}
+
+// A leaf that can still be split during best-first growth, together with its rows and best split:
+pub(crate) struct Candidate {
+    weighted_impurity_decrease: f64,
+    path: Vec<NodePosition>,
+    lf: LazyFrame,
+    best_column: DataFrame,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.weighted_impurity_decrease
+            .total_cmp(&other.weighted_impurity_decrease)
+    }
+}
+
+// The nodes of the classification and the regression tree, which only differ in what a leaf
+// predicts and how the impurity is measured:
+pub(crate) trait TreeNode: Sized {
+    fn get_split_rule(&self) -> Option<&SplitRule>;
+    fn get_split_rule_mut(&mut self) -> Option<&mut SplitRule>;
+    fn set_split_rule(&mut self, split_rule: SplitRule);
+    fn get_child(&self, node_position: NodePosition) -> Option<&Self>;
+    fn get_child_mut(&mut self, node_position: NodePosition) -> Option<&mut Self>;
+    fn spawn_child(&mut self, node_position: NodePosition) -> Result<(), TreeError>;
+    fn get_settings(&self) -> Settings;
+    fn get_depth(&self) -> u8;
+    fn get_n_samples(&self) -> u128;
+    fn get_impurity(&self) -> f64;
+    fn is_final(&self) -> bool;
+    fn set_final(&mut self);
+
+    // The prediction of a leaf, without an alias:
+    fn get_leaf_expr(&self) -> Result<Expr, TreeError>;
+
+    // Sets the prediction, the number of rows and the impurity of a node from its rows:
+    fn fit_leaf(&mut self, lf: &LazyFrame, target_column: &str) -> Result<(), TreeError>;
+
+    // The best split of every feature tried, sorted from the lowest impurity of the children:
+    fn get_impurity_for_all_columns(
+        &self,
+        lf: LazyFrame,
+        state: &mut GrowthState,
+    ) -> Result<LazyFrame, TreeError>;
+
+    fn grow(&mut self, lf: LazyFrame, target_column: &str) -> Result<(), TreeError> {
+        // The random generator is only used when a subset of the features is tried at every split:
+        let settings = self.get_settings();
+        let mut state = GrowthState {
+            target_column,
+            rng: StdRng::seed_from_u64(settings.get_seed()),
+            n_leaves: 1,
+        };
+        match settings.get_tree_growth() {
+            TreeGrowth::DepthFirst => self.private_fit(lf, &mut state),
+            TreeGrowth::BestFirst => self.fit_best_first(lf, &mut state),
+        }
+    }
+
+    // Steps 1 and 2 of fitting a node: what it predicts and, unless final, its best split:
+    fn find_split(
+        &mut self,
+        lf: &LazyFrame,
+        state: &mut GrowthState,
+    ) -> Result<Option<DataFrame>, TreeError> {
+        self.fit_leaf(lf, state.target_column)?;
+        let settings = self.get_settings();
+
+        // Step 1: Am I a final node?
+        if self.get_depth() == settings.get_max_depth()
+            || self.is_final()
+            || self.get_n_samples() < settings.get_min_samples_split()
+            || state.is_leaf_budget_spent(settings)
+        {
+            self.set_final();
+            return Ok(None);
+        }
+
+        // Step 2: Get the split criterion, a node without any valid split becomes final:
+        let impurity_lf = self.get_impurity_for_all_columns(lf.clone(), state)?;
+        let best_column = impurity_lf.first().collect()?;
+        if best_column.height() == 0 {
+            self.set_final();
+            return Ok(None);
+        }
+
+        // A split that doesn't lower the impurity enough is not worth making:
+        let impurity_decrease = self.get_impurity() - get_child_impurity(&best_column)?;
+        if impurity_decrease < settings.get_min_impurity_decrease() {
+            self.set_final();
+            return Ok(None);
+        }
+        Ok(Some(best_column))
+    }
+
+    // Step 3: Create left/right node and split the rows between them:
+    fn split(
+        &mut self,
+        lf: LazyFrame,
+        best_column: &DataFrame,
+        state: &mut GrowthState,
+    ) -> Result<(LazyFrame, LazyFrame), TreeError> {
+        let (sample_size_left, sample_size_right) = get_size_of_left_and_right(best_column)?;
+        self.set_split_rule(SplitRule::from_best_split(best_column)?);
+        self.spawn_child(NodePosition::Left)?;
+        self.spawn_child(NodePosition::Right)?;
+        state.n_leaves += 1;
+
+        let min_leave_size = self.get_settings().get_min_leave_size();
+        for (node_position, sample_size) in [
+            (NodePosition::Left, sample_size_left),
+            (NodePosition::Right, sample_size_right),
+        ] {
+            if let Some(child) = self.get_child_mut(node_position) {
+                if sample_size < min_leave_size {
+                    child.set_final();
+                }
+            }
+        }
+        self.split_lazyframe_left_right(lf)
+    }
+
+    fn private_fit(&mut self, lf: LazyFrame, state: &mut GrowthState) -> Result<(), TreeError> {
+        let lf = lf.cache();
+        let Some(best_column) = self.find_split(&lf, state)? else {
+            return Ok(());
+        };
+        let (left_lf, right_lf) = self.split(lf, &best_column, state)?;
+
+        // Step 4: Fit children, the left subtree is grown completely before the right one:
+        if let Some(left_node) = self.get_child_mut(NodePosition::Left) {
+            left_node.private_fit(left_lf, state)?;
+        }
+        if let Some(right_node) = self.get_child_mut(NodePosition::Right) {
+            right_node.private_fit(right_lf, state)?;
+        }
+        Ok(())
+    }
+
+    // Always splits the leaf with the largest decrease in impurity next:
+    fn fit_best_first(&mut self, lf: LazyFrame, state: &mut GrowthState) -> Result<(), TreeError> {
+        let mut candidates = BinaryHeap::new();
+        self.push_candidate(&mut candidates, lf, Vec::new(), state)?;
+        while let Some(candidate) = candidates.pop() {
+            let node = self.get_node_mut(&candidate.path)?;
+            // Leaves that remain once the budget is spent are final:
+            if state.is_leaf_budget_spent(node.get_settings()) {
+                node.set_final();
+                continue;
+            }
+            let (left_lf, right_lf) = node.split(candidate.lf, &candidate.best_column, state)?;
+            for (position, child_lf) in [
+                (NodePosition::Left, left_lf),
+                (NodePosition::Right, right_lf),
+            ] {
+                let mut path = candidate.path.clone();
+                path.push(position);
+                self.push_candidate(&mut candidates, child_lf, path, state)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn push_candidate(
+        &mut self,
+        candidates: &mut BinaryHeap<Candidate>,
+        lf: LazyFrame,
+        path: Vec<NodePosition>,
+        state: &mut GrowthState,
+    ) -> Result<(), TreeError> {
+        let lf = lf.cache();
+        let node = self.get_node_mut(&path)?;
+        if let Some(best_column) = node.find_split(&lf, state)? {
+            // Weighted by the number of rows, as a split of a large node matters more:
+            let impurity_decrease = node.get_impurity() - get_child_impurity(&best_column)?;
+            candidates.push(Candidate {
+                weighted_impurity_decrease: node.get_n_samples() as f64 * impurity_decrease,
+                path,
+                lf,
+                best_column,
+            });
+        }
+        Ok(())
+    }
+
+    fn get_node_mut(&mut self, path: &[NodePosition]) -> Result<&mut Self, TreeError> {
+        let mut node = self;
+        for node_position in path {
+            node = node
+                .get_child_mut(*node_position)
+                .ok_or(TreeError::NotFitted)?;
+        }
+        Ok(node)
+    }
+
+    // The splits were searched on encoded features, their rules need the encoding to be applied:
+    fn set_encodings(&mut self, preprocessor: &Preprocessor) {
+        if let Some(split_rule) = self.get_split_rule_mut() {
+            split_rule.encoding = preprocessor.get_encoding(&split_rule.feature);
+        }
+        for node_position in [NodePosition::Left, NodePosition::Right] {
+            if let Some(child) = self.get_child_mut(node_position) {
+                child.set_encodings(preprocessor);
+            }
+        }
+    }
+
+    fn split_lazyframe_left_right(
+        &self,
+        lf: LazyFrame,
+    ) -> Result<(LazyFrame, LazyFrame), TreeError> {
+        let split_expression = self
+            .get_split_rule()
+            .ok_or(TreeError::NotFitted)?
+            .to_expr()?;
+        let left_lf = lf.clone().filter(split_expression.clone());
+        let right_lf = lf.filter(not(split_expression));
+        Ok((left_lf, right_lf))
+    }
+
+    fn private_predict(
+        &self,
+        lf: LazyFrame,
+        leaf_expressions: &impl Fn(&Self) -> Result<Vec<Expr>, TreeError>,
+    ) -> Result<LazyFrame, TreeError> {
+        // If self is final, add prediction and return:
+        if self.is_final() {
+            return Ok(lf.with_columns(leaf_expressions(self)?));
+        }
+
+        // If not final, send to child nodes:
+        let (mut left_lf, mut right_lf) = self.split_lazyframe_left_right(lf)?;
+
+        // Get predictions:
+        if let (Some(left), Some(right)) = (
+            self.get_child(NodePosition::Left),
+            self.get_child(NodePosition::Right),
+        ) {
+            left_lf = left.private_predict(left_lf, leaf_expressions)?;
+            right_lf = right.private_predict(right_lf, leaf_expressions)?;
+        }
+
+        // Combine:
+        Ok(concat(vec![left_lf, right_lf], UnionArgs::default())?)
+    }
+
+    fn private_to_expr(&self) -> Result<Expr, TreeError> {
+        match (
+            self.get_split_rule(),
+            self.get_child(NodePosition::Left),
+            self.get_child(NodePosition::Right),
+        ) {
+            (Some(split_rule), Some(left), Some(right)) => Ok(when(split_rule.to_expr()?)
+                .then(left.private_to_expr()?)
+                .otherwise(right.private_to_expr()?)),
+            _ => self.get_leaf_expr(),
+        }
+    }
+}